@@ -0,0 +1,306 @@
+// conditional compilation, run before the IR builder: the declarations and
+// statements with an unsatisfied `#[cfg(...)]` are removed and `cfg!(...)`
+// is replaced by a bool literal
+use std::mem;
+
+use ast::*;
+use ir::builder::{SemanticError, SemanticErrorKind};
+use pipeline::CompileOptions;
+use span::Spanned;
+
+// checked by the IR builder, see also Declaration::doc
+pub const DECLARATION_ATTRIBUTES: &'static [&'static str] = &["doc",
+                                                             "inline",
+                                                             "noinline",
+                                                             "cold",
+                                                             "export_name",
+                                                             "link_name",
+                                                             "packed"];
+
+pub fn strip_translation_unit(tu: &mut TranslationUnit,
+                              options: &CompileOptions)
+                              -> Result<(), SemanticError> {
+    let declarations = mem::replace(&mut tu.declarations, Vec::new());
+    for mut decl in declarations {
+        if strip_attributes(decl.inner.attributes_mut(), options)? {
+            strip_declaration(&mut decl.inner, options)?;
+            tu.declarations.push(decl);
+        }
+    }
+    Ok(())
+}
+
+// false if one of the cfg attributes is not satisfied, the other known
+// attributes are kept
+fn strip_attributes(attributes: &mut Vec<Spanned<Meta>>,
+                    options: &CompileOptions)
+                    -> Result<bool, SemanticError> {
+    let mut enabled = true;
+    for attribute in mem::replace(attributes, Vec::new()) {
+        match attribute.inner {
+            Meta::List(ref name, ref items) if name == "cfg" && items.len() == 1 => {
+                enabled &= eval_predicate(&items[0], options)?;
+            }
+            ref meta if DECLARATION_ATTRIBUTES.contains(&meta.name()) => {
+                attributes.push(attribute.clone());
+            }
+            ref meta if meta.name() == "cfg" => {
+                return Err(SemanticError {
+                               kind: SemanticErrorKind::MalformedAttribute {
+                                   name: meta.name().to_string(),
+                               },
+                               span: attribute.span,
+                           })
+            }
+            ref meta => {
+                return Err(SemanticError {
+                               kind: SemanticErrorKind::UnknownAttribute {
+                                   name: meta.name().to_string(),
+                               },
+                               span: attribute.span,
+                           })
+            }
+        }
+    }
+    Ok(enabled)
+}
+
+// `key`, `key = "value"`, `not(p)`, `all(p, ...)` or `any(p, ...)`
+fn eval_predicate(pred: &Spanned<Meta>, options: &CompileOptions) -> Result<bool, SemanticError> {
+    match pred.inner {
+        Meta::Word(ref key) => Ok(options.cfg_enabled(key, None)),
+        Meta::NameValue(ref key, ref value) => Ok(options.cfg_enabled(key, Some(value))),
+        Meta::List(ref name, ref items) if name == "all" || name == "any" ||
+                                           (name == "not" && items.len() == 1) => {
+            let mut values = Vec::with_capacity(items.len());
+            for item in items {
+                values.push(eval_predicate(item, options)?);
+            }
+            Ok(match name.as_str() {
+                   "all" => values.iter().all(|&value| value),
+                   "any" => values.iter().any(|&value| value),
+                   _ => !values[0],
+               })
+        }
+        Meta::List(..) => {
+            Err(SemanticError {
+                    kind: SemanticErrorKind::InvalidCfgPredicate,
+                    span: pred.span,
+                })
+        }
+    }
+}
+
+fn strip_declaration(decl: &mut Declaration, options: &CompileOptions) -> Result<(), SemanticError> {
+    match *decl {
+        Declaration::Function {
+            ref mut params,
+            ref mut stmt,
+            ..
+        } => {
+            for param in params {
+                if let Some(ref mut default) = param.2 {
+                    strip_expression(default, options)?;
+                }
+            }
+            strip_compound_statement(&mut stmt.inner, options)
+        }
+        Declaration::StaticAssert { ref mut cond, .. } => strip_expression(cond, options),
+        Declaration::Const { ref mut expr, .. } => strip_expression(expr, options),
+        Declaration::ExternFunction { .. } |
+        Declaration::Struct { .. } => Ok(()),
+    }
+}
+
+fn strip_compound_statement(cstmt: &mut CompoundStatement,
+                            options: &CompileOptions)
+                            -> Result<(), SemanticError> {
+    let stmts = mem::replace(&mut cstmt.0, Vec::new());
+    for stmt in stmts {
+        if let Some(stmt) = strip_statement(stmt, options)? {
+            cstmt.0.push(stmt);
+        }
+    }
+    if let Some(ref mut expr) = cstmt.1 {
+        strip_expression(expr, options)?;
+    }
+    Ok(())
+}
+
+// None if the statement is removed
+fn strip_statement(mut stmt: Spanned<Statement>,
+                   options: &CompileOptions)
+                   -> Result<Option<Spanned<Statement>>, SemanticError> {
+    match stmt.inner {
+        Statement::Attributed(ref mut attributes, _) => {
+            if !strip_attributes(attributes, options)? {
+                return Ok(None);
+            }
+            // the doc comments of the statements are not kept
+            if let Some(attribute) = attributes.iter().find(|attr| attr.inner.name() != "doc") {
+                return Err(SemanticError {
+                               kind: SemanticErrorKind::MisplacedAttribute {
+                                   name: attribute.inner.name().to_string(),
+                               },
+                               span: attribute.span,
+                           });
+            }
+        }
+        Statement::Compound(ref mut cstmt) |
+        Statement::Loop { stmt: ref mut cstmt, .. } => {
+            strip_compound_statement(&mut cstmt.inner, options)?
+        }
+        Statement::Let { ref mut expr, .. } |
+        Statement::Defer { ref mut expr } |
+        Statement::StaticAssert { cond: ref mut expr, .. } |
+        Statement::Expression { ref mut expr } => strip_expression(expr, options)?,
+        Statement::While {
+            ref mut cond,
+            ref mut stmt,
+            ..
+        } => {
+            strip_expression(cond, options)?;
+            strip_compound_statement(&mut stmt.inner, options)?;
+        }
+        Statement::For {
+            ref mut init_expr,
+            ref mut cond_expr,
+            ref mut step_expr,
+            ref mut stmt,
+            ..
+        } => {
+            strip_expression(init_expr, options)?;
+            strip_expression(cond_expr, options)?;
+            strip_expression(step_expr, options)?;
+            strip_compound_statement(&mut stmt.inner, options)?;
+        }
+        Statement::ForRange {
+            ref mut start_expr,
+            ref mut end_expr,
+            ref mut stmt,
+            ..
+        } => {
+            strip_expression(start_expr, options)?;
+            strip_expression(end_expr, options)?;
+            strip_compound_statement(&mut stmt.inner, options)?;
+        }
+        Statement::If {
+            ref mut if_branch,
+            ref mut elseif_branches,
+            ref mut else_branch,
+        } => {
+            strip_expression(&mut if_branch.0, options)?;
+            strip_compound_statement(&mut if_branch.1.inner, options)?;
+            strip_else_branches(elseif_branches, else_branch, options)?;
+        }
+        Statement::Break { ref mut expr, .. } |
+        Statement::Return { ref mut expr } => {
+            if let Some(ref mut expr) = *expr {
+                strip_expression(expr, options)?;
+            }
+        }
+        Statement::Continue { .. } => {}
+    }
+
+    if let Statement::Attributed(_, sub_stmt) = stmt.inner {
+        strip_statement(*sub_stmt, options)
+    } else {
+        Ok(Some(stmt))
+    }
+}
+
+fn strip_else_branches(elseif_branches: &mut [(Spanned<Expression>, Spanned<CompoundStatement>)],
+                       else_branch: &mut Option<Spanned<CompoundStatement>>,
+                       options: &CompileOptions)
+                       -> Result<(), SemanticError> {
+    for &mut (ref mut cond, ref mut stmt) in elseif_branches {
+        strip_expression(cond, options)?;
+        strip_compound_statement(&mut stmt.inner, options)?;
+    }
+    if let Some(ref mut stmt) = *else_branch {
+        strip_compound_statement(&mut stmt.inner, options)?;
+    }
+    Ok(())
+}
+
+fn strip_expression(expr: &mut Spanned<Expression>,
+                    options: &CompileOptions)
+                    -> Result<(), SemanticError> {
+    let value = match expr.inner {
+        Expression::MacroCall(ref name, ref pred) => {
+            if name.inner != "cfg" {
+                return Err(SemanticError {
+                               kind: SemanticErrorKind::UnknownMacro { name: name.inner.clone() },
+                               span: name.span,
+                           });
+            }
+            eval_predicate(pred, options)?
+        }
+        ref mut other => return strip_sub_expressions(other, options),
+    };
+    expr.inner = Expression::Literal(Literal::Bool(value));
+    Ok(())
+}
+
+fn strip_sub_expressions(expr: &mut Expression,
+                         options: &CompileOptions)
+                         -> Result<(), SemanticError> {
+    match *expr {
+        Expression::Assign(_, ref mut lhs, ref mut rhs) |
+        Expression::Subscript(ref mut lhs, ref mut rhs) |
+        Expression::BinOp(_, ref mut lhs, ref mut rhs) |
+        Expression::ArrayDefaultLiteral(ref mut lhs, ref mut rhs) => {
+            strip_expression(lhs, options)?;
+            strip_expression(rhs, options)
+        }
+        Expression::UnOp(_, ref mut sub_expr) |
+        Expression::Cast(ref mut sub_expr, _) |
+        Expression::FieldAccess(ref mut sub_expr, _) |
+        Expression::Paren(ref mut sub_expr) |
+        Expression::NamedArg(_, ref mut sub_expr) |
+        Expression::NewArray(_, ref mut sub_expr) |
+        Expression::Delete(ref mut sub_expr) |
+        Expression::VaArg(ref mut sub_expr, _) => strip_expression(sub_expr, options),
+        Expression::FuncCall(ref mut func, ref mut args) => {
+            strip_expression(func, options)?;
+            for arg in args {
+                strip_expression(arg, options)?;
+            }
+            Ok(())
+        }
+        Expression::ArrayFullLiteral(ref mut items) => {
+            for item in items {
+                strip_expression(item, options)?;
+            }
+            Ok(())
+        }
+        Expression::StructLiteral(ref mut lit) |
+        Expression::NewStruct(ref mut lit) => {
+            for field in &mut lit.fields {
+                strip_expression(&mut field.inner.1, options)?;
+            }
+            if let Some(ref mut base) = lit.base {
+                strip_expression(base, options)?;
+            }
+            Ok(())
+        }
+        Expression::If {
+            ref mut if_branch,
+            ref mut elseif_branches,
+            ref mut else_branch,
+        } => {
+            strip_expression(&mut if_branch.0, options)?;
+            strip_compound_statement(&mut if_branch.1.inner, options)?;
+            strip_else_branches(elseif_branches, else_branch, options)
+        }
+        Expression::Block(ref mut cstmt) |
+        Expression::Loop { stmt: ref mut cstmt, .. } => {
+            strip_compound_statement(&mut cstmt.inner, options)
+        }
+        Expression::Identifier(_) |
+        Expression::Literal(_) |
+        Expression::StringLiteral(_) |
+        Expression::SizeOf(_) => Ok(()),
+        Expression::MacroCall(..) => unreachable!(), // see strip_expression
+    }
+}
@@ -2,6 +2,7 @@ use std::fmt;
 use span::Spanned;
 
 pub mod printer;
+pub mod cfg;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct TranslationUnit {
@@ -9,6 +10,9 @@ pub struct TranslationUnit {
     pub declarations: Vec<Spanned<Declaration>>,
 }
 
+// (name, type, default value)
+pub type Param = (Spanned<String>, Spanned<ParseType>, Option<Spanned<Expression>>);
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Declaration {
     ExternFunction {
@@ -16,51 +20,167 @@ pub enum Declaration {
         params: Vec<Spanned<ParseType>>,
         variadic: bool,
         return_ty: Spanned<ParseType>,
+        attributes: Vec<Spanned<Meta>>,
     },
     Function {
         name: String,
-        params: Vec<(Spanned<String>, Spanned<ParseType>)>,
+        params: Vec<Param>,
+        variadic: bool,
         return_ty: Spanned<ParseType>,
         stmt: Spanned<CompoundStatement>,
+        attributes: Vec<Spanned<Meta>>,
     },
     Struct {
         name: String,
         fields: Vec<(Spanned<String>, Spanned<ParseType>)>,
+        attributes: Vec<Spanned<Meta>>,
+    },
+    StaticAssert {
+        cond: Spanned<Expression>,
+        message: Option<String>,
+        attributes: Vec<Spanned<Meta>>,
+    },
+    Const {
+        name: String,
+        ty: Option<Spanned<ParseType>>,
+        expr: Spanned<Expression>,
+        attributes: Vec<Spanned<Meta>>,
+    },
+}
+
+impl Declaration {
+    pub fn attributes(&self) -> &[Spanned<Meta>] {
+        match *self {
+            Declaration::ExternFunction { ref attributes, .. } |
+            Declaration::Function { ref attributes, .. } |
+            Declaration::Struct { ref attributes, .. } |
+            Declaration::StaticAssert { ref attributes, .. } |
+            Declaration::Const { ref attributes, .. } => attributes,
+        }
+    }
+
+    // the `///` comments (or `doc` attributes), one line each
+    pub fn doc(&self) -> Option<String> {
+        let lines: Vec<&str> = self.attributes()
+            .iter()
+            .filter_map(|attribute| match attribute.inner {
+                            Meta::NameValue(ref name, ref text) if name == "doc" => {
+                                let skip = if text.starts_with(' ') { 1 } else { 0 };
+                                Some(&text[skip..])
+                            }
+                            _ => None,
+                        })
+            .collect();
+        if lines.is_empty() {
+            None
+        } else {
+            Some(lines.join("\n"))
+        }
+    }
+
+    pub fn attributes_mut(&mut self) -> &mut Vec<Spanned<Meta>> {
+        match *self {
+            Declaration::ExternFunction { ref mut attributes, .. } |
+            Declaration::Function { ref mut attributes, .. } |
+            Declaration::Struct { ref mut attributes, .. } |
+            Declaration::StaticAssert { ref mut attributes, .. } |
+            Declaration::Const { ref mut attributes, .. } => attributes,
+        }
     }
 }
 
+// the content of `#[...]` and of cfg predicates
 #[derive(Debug, Clone, PartialEq)]
-pub struct CompoundStatement(pub Vec<Spanned<Statement>>);
+pub enum Meta {
+    Word(String), // name
+    NameValue(String, String), // name = "value"
+    List(String, Vec<Spanned<Meta>>), // name(items, ...)
+}
+
+impl Meta {
+    pub fn name(&self) -> &str {
+        match *self {
+            Meta::Word(ref name) |
+            Meta::NameValue(ref name, _) |
+            Meta::List(ref name, _) => name,
+        }
+    }
+}
+
+impl fmt::Display for Meta {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Meta::Word(ref name) => write!(f, "{}", name),
+            Meta::NameValue(ref name, ref value) => write!(f, "{} = \"{}\"", name, value),
+            Meta::List(ref name, ref items) => {
+                write!(f, "{}(", name)?;
+                for (index, item) in items.iter().enumerate() {
+                    if index != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", item.inner)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompoundStatement(pub Vec<Spanned<Statement>>, pub Option<Box<Spanned<Expression>>>); // (statements, trailing expression)
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Statement {
     Compound(Spanned<CompoundStatement>),
     Let {
         name: String,
+        mutable: bool, // `let mut`
         ty: Option<Spanned<ParseType>>,
         expr: Spanned<Expression>,
     },
-    Loop { stmt: Spanned<CompoundStatement> },
+    Loop {
+        label: Option<String>,
+        stmt: Spanned<CompoundStatement>,
+    },
     While {
+        label: Option<String>,
         cond: Spanned<Expression>,
         stmt: Spanned<CompoundStatement>,
     },
     For {
+        label: Option<String>,
         name: String,
         init_expr: Spanned<Expression>,
         cond_expr: Spanned<Expression>,
         step_expr: Spanned<Expression>,
         stmt: Spanned<CompoundStatement>,
     },
+    ForRange {
+        label: Option<String>,
+        name: Spanned<String>,
+        start_expr: Spanned<Expression>,
+        end_expr: Spanned<Expression>,
+        inclusive: bool,
+        stmt: Spanned<CompoundStatement>,
+    },
     If {
         if_branch: (Spanned<Expression>, Spanned<CompoundStatement>),
         elseif_branches: Vec<(Spanned<Expression>, Spanned<CompoundStatement>)>,
         else_branch: Option<Spanned<CompoundStatement>>,
     },
-    Break,
-    Continue,
+    Break {
+        label: Option<String>,
+        expr: Option<Spanned<Expression>>,
+    },
+    Continue { label: Option<String> },
     Return { expr: Option<Spanned<Expression>> },
+    Defer { expr: Spanned<Expression> },
+    StaticAssert {
+        cond: Spanned<Expression>,
+        message: Option<String>,
+    },
     Expression { expr: Spanned<Expression> },
+    Attributed(Vec<Spanned<Meta>>, Box<Spanned<Statement>>), // removed by ast::cfg
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -70,6 +190,7 @@ pub enum Expression {
     BinOp(BinOpCode, Box<Spanned<Expression>>, Box<Spanned<Expression>>),
     UnOp(UnOpCode, Box<Spanned<Expression>>),
     FuncCall(Box<Spanned<Expression>>, Vec<Spanned<Expression>>),
+    NamedArg(Spanned<String>, Box<Spanned<Expression>>), // only in the arguments of a call
     Cast(Box<Spanned<Expression>>, Spanned<ParseType>),
     FieldAccess(Box<Spanned<Expression>>, Spanned<String>),
     Paren(Box<Spanned<Expression>>),
@@ -77,8 +198,24 @@ pub enum Expression {
     Literal(Literal),
     StringLiteral(String),
     ArrayFullLiteral(Vec<Spanned<Expression>>),
-    ArrayDefaultLiteral(Box<Spanned<Expression>>, i64),
+    ArrayDefaultLiteral(Box<Spanned<Expression>>, Box<Spanned<Expression>>), // (value, length)
     StructLiteral(StructLiteral),
+    NewStruct(StructLiteral),
+    NewArray(Spanned<ParseType>, Box<Spanned<Expression>>), // (element type, length)
+    Delete(Box<Spanned<Expression>>),
+    VaArg(Box<Spanned<Expression>>, Spanned<ParseType>),
+    SizeOf(Spanned<ParseType>),
+    MacroCall(Spanned<String>, Box<Spanned<Meta>>), // removed by ast::cfg
+    If {
+        if_branch: (Box<Spanned<Expression>>, Spanned<CompoundStatement>),
+        elseif_branches: Vec<(Spanned<Expression>, Spanned<CompoundStatement>)>,
+        else_branch: Option<Spanned<CompoundStatement>>,
+    },
+    Block(Spanned<CompoundStatement>),
+    Loop {
+        label: Option<String>,
+        stmt: Spanned<CompoundStatement>,
+    },
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -87,6 +224,7 @@ pub enum Literal {
     Double(f64),
     Bool(bool),
     Char(String),
+    Byte(String),
     Unit,
 }
 
@@ -94,6 +232,7 @@ pub enum Literal {
 pub struct StructLiteral {
     pub name: String,
     pub fields: Vec<Spanned<(String, Spanned<Expression>)>>,
+    pub base: Option<Box<Spanned<Expression>>>, // `..base` gives the other fields
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -39,12 +39,14 @@ impl ASTPrinter {
                 ref params,
                 ref variadic,
                 ref return_ty,
+                ref attributes,
             } => {
                 println!("ExternFunctionDecl '{}' '{}' var:{:?}",
                          name,
                          return_ty.inner,
                          variadic);
                 self.0 += 1;
+                self.print_attributes(attributes);
                 for param in params {
                     self.print_tab();
                     println!("ParamDecl '{}'", param.inner);
@@ -54,15 +56,26 @@ impl ASTPrinter {
             Function {
                 ref name,
                 ref params,
+                ref variadic,
                 ref return_ty,
                 ref stmt,
+                ref attributes,
             } => {
-                println!("FunctionDecl '{}' '{}'", name, return_ty.inner);
+                println!("FunctionDecl '{}' '{}' var:{:?}",
+                         name,
+                         return_ty.inner,
+                         variadic);
                 self.0 += 1;
+                self.print_attributes(attributes);
                 for param in params {
                     self.print_tab();
                     self.print_span(&param.0.span);
                     println!("ParamDecl '{}':'{}'", param.0.inner, param.1.inner);
+                    if let Some(ref default) = param.2 {
+                        self.0 += 1;
+                        self.print_expression(default);
+                        self.0 -= 1;
+                    }
                 }
                 self.print_compound_statement(stmt);
                 self.0 -= 1;
@@ -70,9 +83,11 @@ impl ASTPrinter {
             Struct {
                 ref name,
                 ref fields,
+                ref attributes,
             } => {
                 println!("StructField '{}'", name);
                 self.0 += 1;
+                self.print_attributes(attributes);
                 for field in fields {
                     self.print_tab();
                     self.print_span(&field.0.span);
@@ -80,6 +95,41 @@ impl ASTPrinter {
                 }
                 self.0 -= 1;
             }
+            StaticAssert {
+                ref cond,
+                ref message,
+                ref attributes,
+            } => {
+                println!("StaticAssertDecl {:?}", message);
+                self.0 += 1;
+                self.print_attributes(attributes);
+                self.print_expression(cond);
+                self.0 -= 1;
+            }
+            Const {
+                ref name,
+                ref ty,
+                ref expr,
+                ref attributes,
+            } => {
+                println!("ConstDecl '{}' '{}'",
+                         name,
+                         ty.as_ref()
+                             .map(|ty| ty.inner.to_string())
+                             .unwrap_or_else(|| String::from("undefined")));
+                self.0 += 1;
+                self.print_attributes(attributes);
+                self.print_expression(expr);
+                self.0 -= 1;
+            }
+        }
+    }
+
+    fn print_attributes(&mut self, attributes: &[Spanned<Meta>]) {
+        for attribute in attributes {
+            self.print_tab();
+            self.print_span(&attribute.span);
+            println!("Attribute '{}'", attribute.inner);
         }
     }
 
@@ -90,6 +140,58 @@ impl ASTPrinter {
         for stmt in &cstmt.inner.0 {
             self.print_statement(stmt);
         }
+        if let Some(ref expr) = cstmt.inner.1 {
+            self.print_expression(expr);
+        }
+        self.0 -= 1;
+    }
+
+    fn print_if_branches(&mut self,
+                         if_branch: (&Spanned<Expression>, &Spanned<CompoundStatement>),
+                         elseif_branches: &[(Spanned<Expression>, Spanned<CompoundStatement>)],
+                         else_branch: &Option<Spanned<CompoundStatement>>) {
+        self.print_tab();
+        println!("IfBranch");
+        self.0 += 1;
+        self.print_expression(if_branch.0);
+        self.print_compound_statement(if_branch.1);
+        self.0 -= 1;
+
+        for &(ref cond, ref stmt) in elseif_branches {
+            self.print_tab();
+            println!("ElseIfBranch");
+            self.0 += 1;
+            self.print_expression(cond);
+            self.print_compound_statement(stmt);
+            self.0 -= 1;
+        }
+
+        if let Some(ref stmt) = *else_branch {
+            self.print_tab();
+            println!("ElseBranch");
+            self.0 += 1;
+            self.print_compound_statement(stmt);
+            self.0 -= 1;
+        }
+    }
+
+    fn print_struct_fields(&mut self, struct_lit: &StructLiteral) {
+        self.0 += 1;
+        for field in &struct_lit.fields {
+            self.print_tab();
+            self.print_span(&field.span);
+            println!("Field '{}'", field.inner.0);
+            self.0 += 1;
+            self.print_expression(&field.inner.1);
+            self.0 -= 1;
+        }
+        if let Some(ref base) = struct_lit.base {
+            self.print_tab();
+            println!("Base");
+            self.0 += 1;
+            self.print_expression(base);
+            self.0 -= 1;
+        }
         self.0 -= 1;
     }
 
@@ -106,39 +208,49 @@ impl ASTPrinter {
             }
             Let {
                 ref name,
+                mutable,
                 ref ty,
                 ref expr,
             } => {
-                println!("LetStmt '{}' '{}'",
+                println!("LetStmt '{}' '{}'{}",
                          name,
                          ty.as_ref()
                              .map(|ty| ty.inner.to_string())
-                             .unwrap_or_else(|| String::from("undefined")));
+                             .unwrap_or_else(|| String::from("undefined")),
+                         if mutable { " mut" } else { "" });
                 self.0 += 1;
                 self.print_expression(expr);
                 self.0 -= 1;
             }
-            Loop { ref stmt } => {
-                println!("LoopStmt");
+            Loop {
+                ref label,
+                ref stmt,
+            } => {
+                println!("LoopStmt{}", label_suffix(label));
                 self.0 += 1;
                 self.print_compound_statement(stmt);
                 self.0 -= 1;
             }
-            While { ref cond, ref stmt } => {
-                println!("WhileStmt");
+            While {
+                ref label,
+                ref cond,
+                ref stmt,
+            } => {
+                println!("WhileStmt{}", label_suffix(label));
                 self.0 += 1;
                 self.print_expression(cond);
                 self.print_compound_statement(stmt);
                 self.0 -= 1;
             }
             For {
+                ref label,
                 ref name,
                 ref init_expr,
                 ref cond_expr,
                 ref step_expr,
                 ref stmt,
             } => {
-                println!("ForStmt '{}'", name);
+                println!("ForStmt '{}'{}", name, label_suffix(label));
                 self.0 += 1;
                 self.print_expression(init_expr);
                 self.print_expression(cond_expr);
@@ -146,6 +258,24 @@ impl ASTPrinter {
                 self.print_compound_statement(stmt);
                 self.0 -= 1;
             }
+            ForRange {
+                ref label,
+                ref name,
+                ref start_expr,
+                ref end_expr,
+                inclusive,
+                ref stmt,
+            } => {
+                println!("ForRangeStmt '{}' inclusive:{:?}{}",
+                         name.inner,
+                         inclusive,
+                         label_suffix(label));
+                self.0 += 1;
+                self.print_expression(start_expr);
+                self.print_expression(end_expr);
+                self.print_compound_statement(stmt);
+                self.0 -= 1;
+            }
             If {
                 ref if_branch,
                 ref elseif_branches,
@@ -153,38 +283,24 @@ impl ASTPrinter {
             } => {
                 println!("IfStmt");
                 self.0 += 1;
-
-                self.print_tab();
-                println!("IfBranch");
-                self.0 += 1;
-                self.print_expression(&if_branch.0);
-                self.print_compound_statement(&if_branch.1);
+                self.print_if_branches((&if_branch.0, &if_branch.1),
+                                       elseif_branches,
+                                       else_branch);
                 self.0 -= 1;
-
-                for &(ref cond, ref stmt) in elseif_branches {
-                    self.print_tab();
-                    println!("ElseIfBranch");
-                    self.0 += 1;
-                    self.print_expression(cond);
-                    self.print_compound_statement(stmt);
-                    self.0 -= 1;
-                }
-
-                if let Some(ref stmt) = *else_branch {
-                    self.print_tab();
-                    println!("ElseBranch");
-                    self.0 += 1;
-                    self.print_compound_statement(stmt);
-                    self.0 -= 1;
+            }
+            Break {
+                ref label,
+                ref expr,
+            } => {
+                println!("BreakStmt{}", label_suffix(label));
+                self.0 += 1;
+                if let Some(ref expr) = *expr {
+                    self.print_expression(expr);
                 }
-
                 self.0 -= 1;
             }
-            Break => {
-                println!("BreakStmt");
-            }
-            Continue => {
-                println!("ContinueStmt");
+            Continue { ref label } => {
+                println!("ContinueStmt{}", label_suffix(label));
             }
             Return { ref expr } => {
                 println!("ReturnStmt");
@@ -194,12 +310,34 @@ impl ASTPrinter {
                 }
                 self.0 -= 1;
             }
+            Defer { ref expr } => {
+                println!("DeferStmt");
+                self.0 += 1;
+                self.print_expression(expr);
+                self.0 -= 1;
+            }
+            StaticAssert {
+                ref cond,
+                ref message,
+            } => {
+                println!("StaticAssertStmt {:?}", message);
+                self.0 += 1;
+                self.print_expression(cond);
+                self.0 -= 1;
+            }
             Expression { ref expr } => {
                 println!("ExprStmt");
                 self.0 += 1;
                 self.print_expression(expr);
                 self.0 -= 1;
             }
+            Attributed(ref attributes, ref stmt) => {
+                println!("AttributedStmt");
+                self.0 += 1;
+                self.print_attributes(attributes);
+                self.print_statement(stmt);
+                self.0 -= 1;
+            }
         }
     }
 
@@ -247,6 +385,12 @@ impl ASTPrinter {
                 }
                 self.0 -= 1;
             }
+            NamedArg(ref name, ref expr) => {
+                println!("NamedArg '{}'", name.inner);
+                self.0 += 1;
+                self.print_expression(expr);
+                self.0 -= 1;
+            }
             Cast(ref expr, ref target_ty) => {
                 println!("CastOp to '{}'", target_ty.inner);
                 self.0 += 1;
@@ -275,6 +419,7 @@ impl ASTPrinter {
                     Literal::Double(val) => println!("DoubleLit '{}'", val),
                     Literal::Bool(val) => println!("BoolLit '{:?}'", val),
                     Literal::Char(ref val) => println!("CharLit '{}'", val),
+                    Literal::Byte(ref val) => println!("ByteLit '{}'", val),
                     Literal::Unit => println!("UnitLit"),
                 }
             }
@@ -289,25 +434,77 @@ impl ASTPrinter {
                 }
                 self.0 -= 1;
             }
-            ArrayDefaultLiteral(ref def, size) => {
-                println!("ArrayDefaultLiteral (size: {})", size);
+            ArrayDefaultLiteral(ref def, ref size) => {
+                println!("ArrayDefaultLiteral");
                 self.0 += 1;
                 self.print_expression(def);
+                self.print_expression(size);
                 self.0 -= 1;
             }
             StructLiteral(ref struct_lit) => {
                 println!("StructLiteral '{}'", struct_lit.name);
+                self.print_struct_fields(struct_lit);
+            }
+            NewStruct(ref struct_lit) => {
+                println!("NewStruct '{}'", struct_lit.name);
+                self.print_struct_fields(struct_lit);
+            }
+            NewArray(ref ty, ref len) => {
+                println!("NewArray '{}'", ty.inner);
                 self.0 += 1;
-                for field in &struct_lit.fields {
-                    self.print_tab();
-                    self.print_span(&field.span);
-                    println!("Field '{}'", field.inner.0);
-                    self.0 += 1;
-                    self.print_expression(&field.inner.1);
-                    self.0 -= 1;
-                }
+                self.print_expression(len);
+                self.0 -= 1;
+            }
+            Delete(ref expr) => {
+                println!("DeleteExpr");
+                self.0 += 1;
+                self.print_expression(expr);
+                self.0 -= 1;
+            }
+            SizeOf(ref ty) => println!("SizeOf '{}'", ty.inner),
+            MacroCall(ref name, ref meta) => {
+                println!("MacroCall '{}!({})'", name.inner, meta.inner)
+            }
+            VaArg(ref list, ref ty) => {
+                println!("VaArg '{}'", ty.inner);
+                self.0 += 1;
+                self.print_expression(list);
+                self.0 -= 1;
+            }
+            If {
+                ref if_branch,
+                ref elseif_branches,
+                ref else_branch,
+            } => {
+                println!("IfExpr");
+                self.0 += 1;
+                self.print_if_branches((&if_branch.0, &if_branch.1),
+                                       elseif_branches,
+                                       else_branch);
+                self.0 -= 1;
+            }
+            Block(ref cstmt) => {
+                println!("BlockExpr");
+                self.0 += 1;
+                self.print_compound_statement(cstmt);
+                self.0 -= 1;
+            }
+            Loop {
+                ref label,
+                ref stmt,
+            } => {
+                println!("LoopExpr{}", label_suffix(label));
+                self.0 += 1;
+                self.print_compound_statement(stmt);
                 self.0 -= 1;
             }
         }
     }
 }
+
+fn label_suffix(label: &Option<String>) -> String {
+    label
+        .as_ref()
+        .map(|label| format!(" label:'{}", label))
+        .unwrap_or_default()
+}
@@ -2,64 +2,176 @@ use std::collections::HashMap;
 use itertools::Itertools;
 
 use ir;
+use ir::runtime;
 use std::io::prelude::*;
 use std::io;
 
 pub fn gen_translation_unit<F: Write>(f: &mut F, tu: ir::TranslationUnit) -> io::Result<()> {
-    let mut globals = HashMap::new();
-    for decl in &tu.declarations {
-        register_declaration(decl, &mut globals);
-    }
+    let mut globals = register_declarations(&tu.declarations);
 
+    let mut strings = Vec::new();
     for declaration in tu.declarations {
-        gen_declaration(f, declaration, &mut globals)?;
+        gen_declaration(f, declaration, &mut globals, &mut strings)?;
+    }
+
+    writeln!(f, "declare void @llvm.va_start(i8*)")?;
+    writeln!(f, "declare void @llvm.va_end(i8*)")?;
+
+    for (id, string) in strings.into_iter().enumerate() {
+        writeln!(f,
+                 "@str.{} = private unnamed_addr constant [{} x i8] c\"{}\\00\"",
+                 id,
+                 string.len() + 1,
+                 escape_string(&string))?;
     }
     Ok(())
 }
 
-fn register_declaration(decl: &ir::Declaration, globals: &mut HashMap<String, ir::Type>) {
+fn escape_string(string: &[u8]) -> String {
+    string
+        .iter()
+        .map(|&c| if c >= b' ' && c <= b'~' && c != b'"' && c != b'\\' {
+                 (c as char).to_string()
+             } else {
+                 format!("\\{:02X}", c)
+             })
+        .collect()
+}
+
+// the symbol of each overload of the globals
+type Globals = HashMap<(String, ir::FunctionType), String>;
+
+fn signature(decl: &ir::Declaration) -> (&String, &ir::FunctionType, &[ir::Attribute]) {
     match *decl {
-        ir::Declaration::ExternFunction { ref name, ref ty } |
-        ir::Declaration::Function { ref name, ref ty, .. } => {
-            globals.insert(name.clone(), ir::Type::Function(ty.clone()));
+        ir::Declaration::ExternFunction {
+            ref name,
+            ref ty,
+            ref attributes,
+        } |
+        ir::Declaration::Function {
+            ref name,
+            ref ty,
+            ref attributes,
+            ..
+        } => (name, ty, attributes),
+    }
+}
+
+// the overloaded functions are mangled with their parameter types, unless they
+// have an explicit symbol, the extern functions and the entry point never are
+fn register_declarations(declarations: &[ir::Declaration]) -> Globals {
+    let mut overloads = HashMap::new();
+    for decl in declarations {
+        *overloads.entry(signature(decl).0).or_insert(0) += 1;
+    }
+
+    let mut globals = HashMap::new();
+    for decl in declarations {
+        let (name, ty, attributes) = signature(decl);
+        let symbol = ir::Attribute::symbol(name, attributes);
+        let mangled = match *decl {
+            ir::Declaration::Function { .. } => {
+                symbol == name && overloads[name] > 1 &&
+                !(name == "main" && ty.params_ty.is_empty())
+            }
+            ir::Declaration::ExternFunction { .. } => false,
+        };
+        let symbol = if mangled {
+            mangle(name, ty)
+        } else {
+            symbol.to_string()
+        };
+        globals.insert((name.clone(), ty.clone()), quote_symbol(&symbol));
+    }
+    globals
+}
+
+// like add(int, int)
+fn mangle(name: &str, ty: &ir::FunctionType) -> String {
+    format!("{}({}{})",
+            name,
+            ty.params_ty.iter().join(", "),
+            if ty.variadic { ", .." } else { "" })
+}
+
+// written after @, quoted unless the symbol is a plain identifier
+fn quote_symbol(symbol: &str) -> String {
+    let plain = symbol
+        .chars()
+        .enumerate()
+        .all(|(index, c)| {
+                 c.is_ascii_alphabetic() || c == '_' || c == '.' || c == '$' ||
+                 (index > 0 && c.is_ascii_digit())
+             });
+    if plain && !symbol.is_empty() {
+        return symbol.to_string();
+    }
+
+    let mut quoted = String::from("\"");
+    for byte in symbol.bytes() {
+        if byte == b'"' || byte == b'\\' || !(0x20..0x7F).contains(&byte) {
+            quoted.push_str(&format!("\\{:02X}", byte));
+        } else {
+            quoted.push(byte as char);
         }
     }
+    quoted.push('"');
+    quoted
+}
+
+fn attributes_to_string(attributes: &[ir::Attribute]) -> String {
+    attributes
+        .iter()
+        .filter_map(|attribute| match *attribute {
+                        ir::Attribute::Inline => Some(" inlinehint"),
+                        ir::Attribute::NoInline => Some(" noinline"),
+                        ir::Attribute::Cold => Some(" cold"),
+                        ir::Attribute::ExportName(_) |
+                        ir::Attribute::LinkName(_) => None, // see register_declaration
+                    })
+        .collect()
 }
 
 fn gen_declaration<F: Write>(f: &mut F,
                              declaration: ir::Declaration,
-                             globals: &mut HashMap<String, ir::Type>)
+                             globals: &mut Globals,
+                             strings: &mut Vec<Vec<u8>>)
                              -> io::Result<()> {
     match declaration {
-        ir::Declaration::ExternFunction { name, ty } => {
+        ir::Declaration::ExternFunction { name, ty, attributes } => {
+            let symbol = globals[&(name, ty.clone())].clone();
             writeln!(f,
-                     "declare {} @{}({}{})",
+                     "declare {} @{}({}){}",
                      type_to_string(*ty.return_ty),
-                     name,
-                     ty.params_ty.into_iter().map(type_to_string).join(", "),
-                     if ty.variadic { ", ..." } else { "" })?;
+                     symbol,
+                     params_to_string(ty.params_ty.into_iter().map(type_to_string), ty.variadic),
+                     attributes_to_string(&attributes))?;
         }
         ir::Declaration::Function {
             name,
             ty,
             locals,
             bbs,
+            attributes,
         } => {
+            let symbol = globals[&(name, ty.clone())].clone();
+            let params = ty.params_ty
+                .into_iter()
+                .map(type_to_string)
+                .enumerate()
+                .map(|(index, ty)| format!("{} %arg{}", ty, index));
             writeln!(f,
-                     "define {} @{}({}) {{\nentry:",
+                     "define {} @{}({}){} {{\nentry:",
                      type_to_string(*ty.return_ty),
-                     name,
-                     ty.params_ty
-                         .into_iter()
-                         .map(type_to_string)
-                         .enumerate()
-                         .map(|(index, ty)| format!("{} %arg{}", ty, index))
-                         .join(", "))?;
+                     symbol,
+                     params_to_string(params, ty.variadic),
+                     attributes_to_string(&attributes))?;
 
             let mut function_generator = FunctionGenerator {
                 var_writer: Vec::new(),
                 writer: Vec::new(),
                 globals: globals,
+                strings: strings,
                 locals: HashMap::new(),
             };
             for local in locals {
@@ -84,7 +196,8 @@ fn gen_declaration<F: Write>(f: &mut F,
 struct FunctionGenerator<'a> {
     var_writer: Vec<u8>,
     writer: Vec<u8>,
-    globals: &'a mut HashMap<String, ir::Type>,
+    globals: &'a mut Globals,
+    strings: &'a mut Vec<Vec<u8>>, // emitted as constants after the functions
     locals: HashMap<ir::LocalVarId, ir::Type>,
 }
 
@@ -134,6 +247,14 @@ impl<'a> FunctionGenerator<'a> {
                          type_to_string(dest.ty),
                          dest.id)
             }
+            ir::Statement::Assign(dest, ir::Expression::VaStart) => {
+                // enough room for the va_list of every supported target
+                writeln!(self.var_writer, "\t%va_list_{} = alloca [4 x i64]", dest.id)?;
+                writeln!(self.writer,
+                         "\t%temp_{0} = bitcast [4 x i64]* %va_list_{0} to i8*",
+                         dest.id)?;
+                writeln!(self.writer, "\tcall void @llvm.va_start(i8* %temp_{})", dest.id)
+            }
             ir::Statement::Assign(dest, expr) => {
                 if dest.ty != ir::Type::Unit {
                     write!(self.writer, "\t%temp_{} = ", dest.id)?;
@@ -155,12 +276,12 @@ impl<'a> FunctionGenerator<'a> {
                        type_to_string(ty),
                        id.0)
             }
-            ir::Expression::GlobalLoad(name) => {
-                let ty = self.globals[&name].clone();
+            ir::Expression::GlobalLoad(name, ty) => {
+                let symbol = self.globals[&(name, ty.clone())].clone();
                 write!(self.writer,
                        "bitcast {0}* @{1} to {0}*",
-                       type_to_string(ty),
-                       name)
+                       type_to_string(ir::Type::Function(ty)),
+                       symbol)
             }
             ir::Expression::LValueLoad(val) => {
                 if let ir::Type::LValue(ty) = val.ty {
@@ -211,6 +332,18 @@ impl<'a> FunctionGenerator<'a> {
                         IntNotEqual => "icmp ne i32",
                         BoolNotEqual => "icmp ne i1",
                         DoubleNotEqual => "fcmp one double",
+                        CharLess => "icmp ult i32",
+                        CharLessEqual => "icmp ule i32",
+                        CharGreater => "icmp ugt i32",
+                        CharGreaterEqual => "icmp uge i32",
+                        CharEqual => "icmp eq i32",
+                        CharNotEqual => "icmp ne i32",
+                        ByteLess => "icmp ult i8",
+                        ByteLessEqual => "icmp ule i8",
+                        ByteGreater => "icmp ugt i8",
+                        ByteGreaterEqual => "icmp uge i8",
+                        ByteEqual => "icmp eq i8",
+                        ByteNotEqual => "icmp ne i8",
                     };
                     write!(self.writer, "{} %temp_{}, %temp_{}", op, lhs.id, rhs.id)
                 }
@@ -261,15 +394,16 @@ impl<'a> FunctionGenerator<'a> {
                     }
                     IntToChar => {
                         write!(self.writer,
-                               "trunc {} %temp_{} to i8",
-                               type_to_string(expr.ty),
+                               "call i32 @{}(i32 %temp_{})",
+                               runtime::CHAR_FROM_INT,
                                expr.id)
                     }
-                    CharToInt => {
-                        write!(self.writer,
-                               "zext {} %temp_{} to i32",
-                               type_to_string(expr.ty),
-                               expr.id)
+                    CharToInt => write!(self.writer, "bitcast i32 %temp_{} to i32", expr.id),
+                    IntToByte | CharToByte => {
+                        write!(self.writer, "trunc i32 %temp_{} to i8", expr.id)
+                    }
+                    ByteToInt | ByteToChar => {
+                        write!(self.writer, "zext i8 %temp_{} to i32", expr.id)
                     }
                     IntToBool => {
                         write!(self.writer,
@@ -328,12 +462,51 @@ impl<'a> FunctionGenerator<'a> {
                                if val { 1 } else { 0 })
                     }
                     ir::Literal::Char(val) => {
+                        write!(self.writer, "select i1 true, i32 {}, i32 0", val as u32)
+                    }
+                    ir::Literal::Byte(val) => {
                         write!(self.writer, "select i1 true, i8 {}, i8 0", val)
                     }
+                    ir::Literal::Str(val) => {
+                        let id = self.strings.len();
+                        let len = val.len();
+                        self.strings.push(val);
+                        write!(self.writer,
+                               "select i1 true, {0} {{ i8* getelementptr ([{1} x i8], \
+                                [{1} x i8]* @str.{2}, i32 0, i32 0), i32 {3} }}, \
+                                {0} zeroinitializer",
+                               type_to_string(ir::Type::Str),
+                               len + 1,
+                               id,
+                               len)
+                    }
                     ir::Literal::Unit => Ok(()),
 
                 }
             }
+            ir::Expression::SizeOf(ty) => {
+                write!(self.writer,
+                       "select i1 true, i32 ptrtoint ({0}* getelementptr ({0}, {0}* null, i32 1) \
+                        to i32), i32 0",
+                       type_to_string(ty))
+            }
+            ir::Expression::PtrCast(val, ty) => {
+                write!(self.writer,
+                       "bitcast {} %temp_{} to {}",
+                       type_to_string(val.ty),
+                       val.id,
+                       type_to_string(ty))
+            }
+            ir::Expression::VaStart => unreachable!(), // see gen_statement
+            ir::Expression::VaArg(val, ty) => {
+                write!(self.writer,
+                       "va_arg i8* %temp_{}, {}",
+                       val.id,
+                       type_to_string(ty))
+            }
+            ir::Expression::VaEnd(val) => {
+                write!(self.writer, "call void @llvm.va_end(i8* %temp_{})", val.id)
+            }
         }
     }
 
@@ -367,24 +540,33 @@ fn type_to_string(ty: ir::Type) -> String {
         ir::Type::Bool => "i1".to_string(), // cause c you know
         ir::Type::Int => "i32".to_string(),
         ir::Type::Double => "double".to_string(),
-        ir::Type::Char => "i8".to_string(),
+        ir::Type::Char => "i32".to_string(),
+        ir::Type::Byte => "i8".to_string(),
+        ir::Type::Str => "{ i8*, i32 }".to_string(),
+        ir::Type::VaList => "i8*".to_string(),
         ir::Type::LValue(sub) => format!("{}*", type_to_string(*sub)),
         ir::Type::Ptr(sub) => format!("{}*", type_to_string(*sub)),
         ir::Type::Function(func) => {
-            format!("{}({}{})",
+            format!("{}({})",
                     type_to_string(*func.return_ty),
-                    func.params_ty
-                        .into_iter()
-                        .map(type_to_string)
-                        .join(", "),
-                    if func.variadic { ", ..." } else { "" })
+                    params_to_string(func.params_ty.into_iter().map(type_to_string),
+                                     func.variadic))
         }
         ir::Type::Struct(st) => {
-            format!("{{ {} }}",
-                    st.fields_ty
-                        .into_iter()
-                        .map(|field| type_to_string(field.1))
-                        .join(", "))
+            let fields = st.fields_ty
+                .into_iter()
+                .map(|field| type_to_string(field.1))
+                .join(", ");
+            if st.packed {
+                format!("<{{ {} }}>", fields)
+            } else {
+                format!("{{ {} }}", fields)
+            }
         }
     }
 }
+
+fn params_to_string<I: Iterator<Item = String>>(params: I, variadic: bool) -> String {
+    let variadic = if variadic { Some("...".to_string()) } else { None };
+    params.chain(variadic).join(", ")
+}
@@ -1,11 +1,11 @@
 use std;
 use std::io::Write;
 
-use lexer::{LexicalError, Token};
+use lexer::{LexicalError, Token, UNATTACHED_DOC_COMMENT};
 use lalrpop_util::ParseError;
 
 use span::Span;
-use ir::builder::SemanticError;
+use ir::builder::{SemanticError, SemanticWarning};
 use source_manager::SourceManager;
 
 #[derive(Debug)]
@@ -40,6 +40,18 @@ impl ToError for ParseError<usize, Token, LexicalError> {
                     }),
                 }
             }
+            ParseError::UnrecognizedToken { token: Some((start, Token::DocComment(_), end)), .. } |
+            ParseError::ExtraToken { token: (start, Token::DocComment(_), end) } => {
+                Error {
+                    source_index: source_index,
+                    msg: UNATTACHED_DOC_COMMENT.to_string(),
+                    lines: get_lines(source_manager, Span {
+                        source_index: source_index,
+                        lo: start,
+                        hi: end
+                    }),
+                }
+            }
             ParseError::UnrecognizedToken { token, expected } => {
                 let mut error = if let Some((start, tok, end)) = token {
                     Error {
@@ -92,6 +104,18 @@ impl ToError for ParseError<usize, Token, LexicalError> {
 }
 
 impl ToError for SemanticError {
+    fn convert(self, source_manager: &SourceManager, source_index: usize) -> Error {
+        let mut spans = vec![self.span];
+        spans.extend(self.kind.related_span());
+        Error {
+            source_index: source_index,
+            msg: self.kind.to_string(),
+            lines: get_spans_lines(source_manager, &spans),
+        }
+    }
+}
+
+impl ToError for SemanticWarning {
     fn convert(self, source_manager: &SourceManager, source_index: usize) -> Error {
         Error {
             source_index: source_index,
@@ -102,13 +126,18 @@ impl ToError for SemanticError {
 }
 
 fn get_lines(source_manager: &SourceManager, span: Span) -> Vec<Line> {
-    let input = source_manager.get_input(span.source_index);
+    get_spans_lines(source_manager, &[span])
+}
+
+// the spans are in the same source, their lines are shown in order
+fn get_spans_lines(source_manager: &SourceManager, spans: &[Span]) -> Vec<Line> {
+    let input = source_manager.get_input(spans[0].source_index);
     let mut arrow = String::with_capacity(input.len());
 
     for (i, c) in input.chars().enumerate() {
         arrow.push(match c {
                        '\n' => '\n',
-                       _ if span.lo <= i && i < span.hi=> '^',
+                       _ if spans.iter().any(|span| span.lo <= i && i < span.hi) => '^',
                        _ => ' ',
                    });
     }
@@ -134,7 +163,17 @@ macro_rules! eprintln {
 }
 
 pub fn print_diagnostic(source_manager: &SourceManager, error: Error) {
-    eprintln!("Error in: {}", source_manager.get_file_path(error.source_index).display());
+    print_with_header(source_manager, "Error", error)
+}
+
+pub fn print_warning(source_manager: &SourceManager, warning: Error) {
+    print_with_header(source_manager, "Warning", warning)
+}
+
+fn print_with_header(source_manager: &SourceManager, header: &str, error: Error) {
+    eprintln!("{} in: {}",
+              header,
+              source_manager.get_file_path(error.source_index).display());
     eprintln!("{}", error.msg);
     for line in error.lines {
         eprintln!("{:<5}: {}", line.n + 1, line.text);
@@ -9,6 +9,11 @@ use elang::pipeline::{CompileOptions, OutputType};
 use elang::diagnostics;
 use elang::source_manager::SourceManager;
 
+fn parse_define(define: &str) -> (String, Option<String>) {
+    let mut parts = define.splitn(2, '=');
+    (parts.next().unwrap().to_string(), parts.next().map(String::from))
+}
+
 fn main() {
     let matches = App::new("Elang Compiler")
         .version("0.1")
@@ -18,6 +23,16 @@ fn main() {
         .arg(Arg::with_name("opt")
                  .short("O")
                  .help("Activate optimizations"))
+        .arg(Arg::with_name("release")
+                 .long("release")
+                 .help("Compile out assertions"))
+        .arg(Arg::with_name("define")
+                 .short("D")
+                 .value_name("KEY[=VALUE]")
+                 .help("Set a configuration key for cfg")
+                 .takes_value(true)
+                 .multiple(true)
+                 .number_of_values(1))
         .arg(Arg::with_name("INPUT")
                  .help("Input file")
                  .required(true)
@@ -44,6 +59,11 @@ fn main() {
         print_ast: matches.is_present("ast"),
         print_ir: matches.is_present("ir"),
         opt: matches.is_present("opt"),
+        release: matches.is_present("release"),
+        cfg: matches
+            .values_of("define")
+            .map(|values| values.map(parse_define).collect())
+            .unwrap_or_else(Vec::new),
         output_type: OutputType::new(matches.value_of("output_type").unwrap_or("check")).unwrap(),
         output_path: matches.value_of("OUTPUT").map(PathBuf::from),
     };
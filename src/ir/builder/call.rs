@@ -0,0 +1,172 @@
+// the arguments of the calls, given by position or by name, and the overloads
+use ast;
+use ir;
+use ir::{GlobalTable, Overload, Param, SymbolTable};
+use span::{Spanned, Span};
+use super::const_eval::{self, CallEvaluator, ConstValue};
+use super::{SemanticError, SemanticErrorKind};
+
+type NamedArg = (Option<Spanned<String>>, Spanned<ast::Expression>);
+
+// the name of each argument, the positional ones come first
+pub fn split_named_args(args: Vec<Spanned<ast::Expression>>)
+                        -> Result<Vec<NamedArg>, SemanticError> {
+    let mut named = false;
+    let mut split = Vec::with_capacity(args.len());
+    for arg in args {
+        match arg.inner {
+            ast::Expression::NamedArg(name, expr) => {
+                named = true;
+                split.push((Some(name), *expr));
+            }
+            _ if named => {
+                return Err(SemanticError {
+                               kind: SemanticErrorKind::PositionalAfterNamedArgument,
+                               span: arg.span,
+                           })
+            }
+            _ => split.push((None, arg)),
+        }
+    }
+    Ok(split)
+}
+
+// the index of the argument given to each parameter, None for its default value,
+// then the variadic arguments
+pub fn match_args(ty: &ir::FunctionType,
+                  params: &[Param],
+                  names: &[Option<Spanned<String>>],
+                  span: Span)
+                  -> Result<Vec<Option<usize>>, SemanticError> {
+    let len = ty.params_ty.len();
+    let positional = names.iter().take_while(|name| name.is_none()).count();
+    if positional > len && !ty.variadic {
+        return Err(SemanticError {
+                       kind: SemanticErrorKind::MismatchingParamLen {
+                           expected: len,
+                           found: names.len(),
+                           missing: Vec::new(),
+                       },
+                       span: span,
+                   });
+    }
+
+    let mut sources: Vec<_> = (0..len).map(|index| if index < positional {
+                                               Some(index)
+                                           } else {
+                                               None
+                                           })
+        .collect();
+    for (index, name) in names.iter().enumerate().skip(positional) {
+        let name = name.as_ref().unwrap(); // see split_named_args
+        let param = params
+            .iter()
+            .position(|param| param.name.as_ref() == Some(&name.inner));
+        let kind = match param {
+            Some(param) if sources[param].is_none() => {
+                sources[param] = Some(index);
+                continue;
+            }
+            Some(_) => SemanticErrorKind::ArgumentAlreadySupplied { name: name.inner.clone() },
+            None => SemanticErrorKind::UnknownParameter { name: name.inner.clone() },
+        };
+        return Err(SemanticError {
+                       kind: kind,
+                       span: name.span,
+                   });
+    }
+
+    let missing: Vec<_> = params
+        .iter()
+        .enumerate()
+        .filter(|&(index, param)| sources[index].is_none() && param.default.is_none())
+        .map(|(index, param)| match param.name {
+                 Some(ref name) => name.clone(),
+                 None => format!("#{}", index + 1),
+             })
+        .collect();
+    if !missing.is_empty() {
+        return Err(SemanticError {
+                       kind: SemanticErrorKind::MismatchingParamLen {
+                           expected: len,
+                           found: names.len(),
+                           missing: missing,
+                       },
+                       span: span,
+                   });
+    }
+
+    sources.extend((len..positional).map(Some));
+    Ok(sources)
+}
+
+// the overload accepting the arguments, a non-variadic one is preferred
+pub fn resolve_overload(name: &str,
+                        overloads: &[Overload],
+                        names: &[Option<Spanned<String>>],
+                        args_ty: &[ir::Type],
+                        span: Span)
+                        -> Result<(Overload, Vec<Option<usize>>), SemanticError> {
+    // the default values have the type of their parameter
+    let mut matches: Vec<_> = overloads
+        .iter()
+        .filter_map(|overload| {
+            let sources = match_args(&overload.ty, &overload.params, names, span).ok()?;
+            let accepted = overload
+                .ty
+                .params_ty
+                .iter()
+                .zip(&sources)
+                .all(|(ty, source)| source.map(|index| args_ty[index] == *ty).unwrap_or(true));
+            if accepted {
+                Some((overload.clone(), sources))
+            } else {
+                None
+            }
+        })
+        .collect();
+    if matches.iter().any(|&(ref overload, _)| !overload.ty.variadic) {
+        matches.retain(|&(ref overload, _)| !overload.ty.variadic);
+    }
+
+    let kind = match matches.len() {
+        1 => return Ok(matches.remove(0)),
+        0 => {
+            SemanticErrorKind::NoMatchingOverload {
+                name: name.to_string(),
+                args_ty: args_ty.to_vec(),
+                candidates: overloads.iter().map(|overload| overload.ty.clone()).collect(),
+            }
+        }
+        _ => {
+            SemanticErrorKind::AmbiguousOverload {
+                name: name.to_string(),
+                candidates: matches.into_iter().map(|(overload, _)| overload.ty).collect(),
+            }
+        }
+    };
+    Err(SemanticError {
+            kind: kind,
+            span: span,
+        })
+}
+
+// evaluated for every call, the locals of the caller are not visible
+pub fn eval_default<C: CallEvaluator>(globals_table: &GlobalTable,
+                                      calls: &mut C,
+                                      ty: &ir::Type,
+                                      default: Spanned<ast::Expression>)
+                                      -> Result<ConstValue, SemanticError> {
+    let span = default.span;
+    let value = const_eval::eval_expression(&SymbolTable::new(globals_table), calls, default)?;
+    if value.ty() != *ty {
+        return Err(SemanticError {
+                       kind: SemanticErrorKind::MismatchingTypesArgument {
+                           expected: ty.clone(),
+                           found: value.ty(),
+                       },
+                       span: span,
+                   });
+    }
+    Ok(value)
+}
@@ -0,0 +1,408 @@
+use std::char;
+
+use ast;
+use ir;
+use ir::SymbolTable;
+use span::{Spanned, Span};
+use super::{build_literal, build_type, SemanticError, SemanticErrorKind};
+use super::call;
+use super::typecheck_defs;
+
+// int follows the i32 wrapping semantics of the generated code
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConstValue {
+    Bool(bool),
+    Int(i32),
+    Double(f64),
+    Char(char),
+    Byte(u8),
+}
+
+impl ConstValue {
+    pub fn ty(&self) -> ir::Type {
+        match *self {
+            ConstValue::Bool(_) => ir::Type::Bool,
+            ConstValue::Int(_) => ir::Type::Int,
+            ConstValue::Double(_) => ir::Type::Double,
+            ConstValue::Char(_) => ir::Type::Char,
+            ConstValue::Byte(_) => ir::Type::Byte,
+        }
+    }
+
+    pub fn to_literal(&self) -> ir::Literal {
+        match *self {
+            ConstValue::Bool(val) => ir::Literal::Bool(val),
+            ConstValue::Int(val) => ir::Literal::Int(val as i64),
+            ConstValue::Double(val) => ir::Literal::Double(val),
+            ConstValue::Char(val) => ir::Literal::Char(val),
+            ConstValue::Byte(val) => ir::Literal::Byte(val),
+        }
+    }
+}
+
+// evaluates the calls of a constant expression
+pub trait CallEvaluator {
+    fn call(&mut self,
+            name: &str,
+            ty: &ir::FunctionType,
+            args: Vec<ConstValue>,
+            span: Span)
+            -> Result<ConstValue, SemanticError>;
+}
+
+pub fn eval_expression<C: CallEvaluator>(symbol_table: &SymbolTable,
+                                         calls: &mut C,
+                       expr: Spanned<ast::Expression>)
+                       -> Result<ConstValue, SemanticError> {
+    let span = expr.span;
+    match expr.inner {
+        ast::Expression::Literal(lit) => {
+            from_literal(build_literal(lit, span)?).ok_or_else(|| non_constant(span))
+        }
+        ast::Expression::Identifier(name) => {
+            if symbol_table.is_mutable(&name).is_some() {
+                Err(non_constant(span)) // locals shadow constants
+            } else if let Some(lit) = symbol_table.globals.get_const(&name) {
+                Ok(from_literal(lit).unwrap())
+            } else if symbol_table.is_defined(&name) {
+                Err(non_constant(span))
+            } else {
+                Err(SemanticError {
+                        kind: SemanticErrorKind::IdentifierUndefined { name: name },
+                        span: span,
+                    })
+            }
+        }
+        ast::Expression::Paren(sub_expr) => eval_expression(symbol_table, calls, *sub_expr),
+        ast::Expression::SizeOf(ty) => {
+            let ty_span = ty.span;
+            let ty = build_type(ty, symbol_table.globals)?;
+            match size_of(&ty) {
+                Some((size, _)) => Ok(ConstValue::Int(size as i32)),
+                None => {
+                    Err(SemanticError {
+                            kind: SemanticErrorKind::UnsizedType { ty: ty },
+                            span: ty_span,
+                        })
+                }
+            }
+        }
+        ast::Expression::BinOp(code, lhs, rhs) => {
+            let lhs_value = eval_expression(symbol_table, calls, *lhs)?;
+            if code == ast::BinOpCode::LogicalAnd || code == ast::BinOpCode::LogicalOr {
+                // short-circuit like the generated code
+                let is_and = code == ast::BinOpCode::LogicalAnd;
+                let rhs_span = rhs.span;
+                return match lhs_value {
+                           ConstValue::Bool(lhs) if lhs != is_and => Ok(ConstValue::Bool(lhs)),
+                           ConstValue::Bool(_) => {
+                               match eval_expression(symbol_table, calls, *rhs)? {
+                                   ConstValue::Bool(rhs) => Ok(ConstValue::Bool(rhs)),
+                                   other => {
+                                       Err(SemanticError {
+                                               kind: SemanticErrorKind::BinaryOperationUndefined {
+                                                   op: code,
+                                                   lhs_ty: ir::Type::Bool,
+                                                   rhs_ty: other.ty(),
+                                               },
+                                               span: rhs_span,
+                                           })
+                                   }
+                               }
+                           }
+                           other => {
+                               Err(SemanticError {
+                                       kind: SemanticErrorKind::BinaryOperationUndefined {
+                                           op: code,
+                                           lhs_ty: other.ty(),
+                                           rhs_ty: ir::Type::Bool,
+                                       },
+                                       span: span,
+                                   })
+                           }
+                       };
+            }
+
+            let rhs_value = eval_expression(symbol_table, calls, *rhs)?;
+            let op = typecheck_defs::binop_tyck(code, &lhs_value.ty(), &rhs_value.ty());
+            if let Some((op, _)) = op {
+                eval_binop(op, lhs_value, rhs_value).map_err(|kind| {
+                    SemanticError {
+                        kind: kind,
+                        span: span,
+                    }
+                })
+            } else {
+                Err(SemanticError {
+                        kind: SemanticErrorKind::BinaryOperationUndefined {
+                            op: code,
+                            lhs_ty: lhs_value.ty(),
+                            rhs_ty: rhs_value.ty(),
+                        },
+                        span: span,
+                    })
+            }
+        }
+        ast::Expression::UnOp(code, sub_expr) => {
+            let sub_value = eval_expression(symbol_table, calls, *sub_expr)?;
+            let op = typecheck_defs::unop_tyck(code, &sub_value.ty());
+            match op.and_then(|(op, _)| eval_unop(op, sub_value)) {
+                Some(value) => Ok(value),
+                None => {
+                    Err(SemanticError {
+                            kind: SemanticErrorKind::UnaryOperationUndefined {
+                                op: code,
+                                expr_ty: sub_value.ty(),
+                            },
+                            span: span,
+                        })
+                }
+            }
+        }
+        ast::Expression::Cast(sub_expr, target_ty) => {
+            let sub_value = eval_expression(symbol_table, calls, *sub_expr)?;
+            let target_ty = build_type(target_ty, symbol_table.globals)?;
+            match typecheck_defs::cast_tyck(&sub_value.ty(), &target_ty) {
+                Some(code) => {
+                    eval_cast(code, sub_value).ok_or_else(|| invalid_char(sub_value, span))
+                }
+                None => {
+                    Err(SemanticError {
+                            kind: SemanticErrorKind::CastUndefined {
+                                expr_ty: sub_value.ty(),
+                                target_ty: target_ty,
+                            },
+                            span: span,
+                        })
+                }
+            }
+        }
+        ast::Expression::FuncCall(func, args) => {
+            let func_span = func.span;
+            let name = match func.inner {
+                ast::Expression::Identifier(name) => name,
+                _ => return Err(non_constant(span)),
+            };
+            if symbol_table.is_mutable(&name).is_some() {
+                return Err(non_constant(span)); // function pointers are not constant
+            }
+            let overloads = match symbol_table.globals.get_overloads(&name) {
+                Some(overloads) => overloads.to_vec(),
+                None if symbol_table.globals.get_const(&name).is_some() => {
+                    return Err(non_constant(span))
+                }
+                None => {
+                    return Err(SemanticError {
+                                   kind: SemanticErrorKind::IdentifierUndefined { name: name },
+                                   span: func_span,
+                               })
+                }
+            };
+            // only scalars can be brought back from the evaluation
+            fn const_callable(ty: &ir::FunctionType) -> bool {
+                match *ty.return_ty {
+                    ir::Type::Bool | ir::Type::Int | ir::Type::Double | ir::Type::Char |
+                    ir::Type::Byte => !ty.variadic,
+                    _ => false,
+                }
+            }
+
+            if overloads.len() == 1 && !const_callable(&overloads[0].ty) {
+                return Err(non_constant(span));
+            }
+
+            let (names, args): (Vec<_>, Vec<_>) = call::split_named_args(args)?
+                .into_iter()
+                .unzip();
+            let mut args_span = Vec::with_capacity(args.len());
+            let mut arg_values = Vec::with_capacity(args.len());
+            for arg in args {
+                args_span.push(arg.span);
+                arg_values.push(eval_expression(symbol_table, calls, arg)?);
+            }
+            let args_ty: Vec<_> = arg_values.iter().map(ConstValue::ty).collect();
+
+            // an overloaded function is resolved once the arguments are evaluated
+            let (overload, sources) = if overloads.len() > 1 {
+                call::resolve_overload(&name, &overloads, &names, &args_ty, span)?
+            } else {
+                let overload = overloads.into_iter().next().unwrap();
+                let sources = call::match_args(&overload.ty, &overload.params, &names, span)?;
+                (overload, sources)
+            };
+            if !const_callable(&overload.ty) {
+                return Err(non_constant(span));
+            }
+
+            let mut values = Vec::with_capacity(sources.len());
+            for ((source, param), param_ty) in
+                sources
+                    .into_iter()
+                    .zip(overload.params)
+                    .zip(&overload.ty.params_ty) {
+                let value = match source {
+                    Some(arg) if args_ty[arg] != *param_ty => {
+                        return Err(SemanticError {
+                                       kind: SemanticErrorKind::MismatchingTypesArgument {
+                                           expected: param_ty.clone(),
+                                           found: args_ty[arg].clone(),
+                                       },
+                                       span: args_span[arg],
+                                   })
+                    }
+                    Some(arg) => arg_values[arg],
+                    None => {
+                        call::eval_default(symbol_table.globals,
+                                           calls,
+                                           param_ty,
+                                           param.default.unwrap())?
+                    }
+                };
+                values.push(value);
+            }
+            calls.call(&name, &overload.ty, values, span)
+        }
+        _ => Err(non_constant(span)),
+    }
+}
+
+pub fn from_literal(lit: ir::Literal) -> Option<ConstValue> {
+    match lit {
+        ir::Literal::Int(val) => Some(ConstValue::Int(val as i32)),
+        ir::Literal::Double(val) => Some(ConstValue::Double(val)),
+        ir::Literal::Bool(val) => Some(ConstValue::Bool(val)),
+        ir::Literal::Char(val) => Some(ConstValue::Char(val)),
+        ir::Literal::Byte(val) => Some(ConstValue::Byte(val)),
+        ir::Literal::Str(_) |
+        ir::Literal::Unit => None,
+    }
+}
+
+// see eval_cast
+pub fn invalid_char(value: ConstValue, span: Span) -> SemanticError {
+    let value = match value {
+        ConstValue::Int(value) => value,
+        _ => unreachable!(),
+    };
+    SemanticError {
+        kind: SemanticErrorKind::InvalidCharCast { value: value },
+        span: span,
+    }
+}
+
+pub fn non_constant(span: Span) -> SemanticError {
+    SemanticError {
+        kind: SemanticErrorKind::NonConstantExpression,
+        span: span,
+    }
+}
+
+// the division by zero and `i32::MIN / -1` are undefined at runtime, they are errors here
+pub fn eval_binop(op: ir::BinOpCode,
+                  lhs: ConstValue,
+                  rhs: ConstValue)
+                  -> Result<ConstValue, SemanticErrorKind> {
+    use ir::BinOpCode::*;
+    use self::ConstValue::*;
+    let value = match (op, lhs, rhs) {
+        (IntDivide, Int(_), Int(0)) |
+        (IntMod, Int(_), Int(0)) => return Err(SemanticErrorKind::ConstDivisionByZero),
+        (IntDivide, Int(i32::MIN), Int(-1)) |
+        (IntMod, Int(i32::MIN), Int(-1)) => return Err(SemanticErrorKind::ConstDivisionOverflow),
+        (IntAdd, Int(lhs), Int(rhs)) => Int(lhs.wrapping_add(rhs)),
+        (IntSub, Int(lhs), Int(rhs)) => Int(lhs.wrapping_sub(rhs)),
+        (IntTimes, Int(lhs), Int(rhs)) => Int(lhs.wrapping_mul(rhs)),
+        (IntDivide, Int(lhs), Int(rhs)) => Int(lhs / rhs),
+        (IntMod, Int(lhs), Int(rhs)) => Int(lhs % rhs),
+        (DoubleAdd, Double(lhs), Double(rhs)) => Double(lhs + rhs),
+        (DoubleSub, Double(lhs), Double(rhs)) => Double(lhs - rhs),
+        (DoubleTimes, Double(lhs), Double(rhs)) => Double(lhs * rhs),
+        (DoubleDivide, Double(lhs), Double(rhs)) => Double(lhs / rhs),
+        (IntLess, Int(lhs), Int(rhs)) => Bool(lhs < rhs),
+        (IntLessEqual, Int(lhs), Int(rhs)) => Bool(lhs <= rhs),
+        (IntGreater, Int(lhs), Int(rhs)) => Bool(lhs > rhs),
+        (IntGreaterEqual, Int(lhs), Int(rhs)) => Bool(lhs >= rhs),
+        (DoubleLess, Double(lhs), Double(rhs)) => Bool(lhs < rhs),
+        (DoubleLessEqual, Double(lhs), Double(rhs)) => Bool(lhs <= rhs),
+        (DoubleGreater, Double(lhs), Double(rhs)) => Bool(lhs > rhs),
+        (DoubleGreaterEqual, Double(lhs), Double(rhs)) => Bool(lhs >= rhs),
+        (IntEqual, Int(lhs), Int(rhs)) => Bool(lhs == rhs),
+        (IntNotEqual, Int(lhs), Int(rhs)) => Bool(lhs != rhs),
+        (DoubleEqual, Double(lhs), Double(rhs)) => Bool(lhs == rhs),
+        (DoubleNotEqual, Double(lhs), Double(rhs)) => Bool(lhs != rhs),
+        (BoolEqual, Bool(lhs), Bool(rhs)) => Bool(lhs == rhs),
+        (BoolNotEqual, Bool(lhs), Bool(rhs)) => Bool(lhs != rhs),
+        (CharLess, Char(lhs), Char(rhs)) => Bool(lhs < rhs),
+        (CharLessEqual, Char(lhs), Char(rhs)) => Bool(lhs <= rhs),
+        (CharGreater, Char(lhs), Char(rhs)) => Bool(lhs > rhs),
+        (CharGreaterEqual, Char(lhs), Char(rhs)) => Bool(lhs >= rhs),
+        (CharEqual, Char(lhs), Char(rhs)) => Bool(lhs == rhs),
+        (CharNotEqual, Char(lhs), Char(rhs)) => Bool(lhs != rhs),
+        (ByteLess, Byte(lhs), Byte(rhs)) => Bool(lhs < rhs),
+        (ByteLessEqual, Byte(lhs), Byte(rhs)) => Bool(lhs <= rhs),
+        (ByteGreater, Byte(lhs), Byte(rhs)) => Bool(lhs > rhs),
+        (ByteGreaterEqual, Byte(lhs), Byte(rhs)) => Bool(lhs >= rhs),
+        (ByteEqual, Byte(lhs), Byte(rhs)) => Bool(lhs == rhs),
+        (ByteNotEqual, Byte(lhs), Byte(rhs)) => Bool(lhs != rhs),
+        _ => unreachable!(),
+    };
+    Ok(value)
+}
+
+// None for the operations on lvalues and pointers
+pub fn eval_unop(op: ir::UnOpCode, value: ConstValue) -> Option<ConstValue> {
+    match (op, value) {
+        (ir::UnOpCode::IntMinus, ConstValue::Int(val)) => Some(ConstValue::Int(val.wrapping_neg())),
+        (ir::UnOpCode::DoubleMinus, ConstValue::Double(val)) => Some(ConstValue::Double(-val)),
+        (ir::UnOpCode::BoolLogicalNot, ConstValue::Bool(val)) => Some(ConstValue::Bool(!val)),
+        _ => None,
+    }
+}
+
+// None if an int is not a code point
+pub fn eval_cast(code: ir::CastCode, value: ConstValue) -> Option<ConstValue> {
+    use ir::CastCode::*;
+    use self::ConstValue::*;
+    let value = match (code, value) {
+        (IntToDouble, Int(val)) => Double(val as f64),
+        (DoubleToInt, Double(val)) => Int(val as i32),
+        (IntToChar, Int(val)) => return char::from_u32(val as u32).map(Char),
+        (CharToInt, Char(val)) => Int(val as i32),
+        (IntToByte, Int(val)) => Byte(val as u8),
+        (ByteToInt, Byte(val)) => Int(val as i32),
+        (ByteToChar, Byte(val)) => Char(val as char),
+        (CharToByte, Char(val)) => Byte(val as u32 as u8),
+        (IntToBool, Int(val)) => Bool(val != 0),
+        (BoolToInt, Bool(val)) => Int(val as i32),
+        _ => unreachable!(),
+    };
+    Some(value)
+}
+
+// (size, alignment) in bytes with the x86-64 layout used by the code generator
+pub fn size_of(ty: &ir::Type) -> Option<(usize, usize)> {
+    match *ty {
+        ir::Type::Bool | ir::Type::Byte => Some((1, 1)),
+        ir::Type::Int | ir::Type::Char => Some((4, 4)),
+        ir::Type::Double | ir::Type::Ptr(_) | ir::Type::VaList => Some((8, 8)),
+        ir::Type::Str => Some((16, 8)),
+        ir::Type::Struct(ref st) => {
+            let mut size = 0;
+            let mut align = 1;
+            for &(_, ref field_ty) in &st.fields_ty {
+                let (field_size, mut field_align) = size_of(field_ty)?;
+                if st.packed {
+                    field_align = 1;
+                }
+                size = align_to(size, field_align) + field_size;
+                align = align.max(field_align);
+            }
+            Some((align_to(size, align), align))
+        }
+        ir::Type::Unit | ir::Type::LValue(_) | ir::Type::Function(_) => None,
+    }
+}
+
+fn align_to(size: usize, align: usize) -> usize {
+    (size + align - 1) / align * align
+}
@@ -0,0 +1,527 @@
+// compile-time evaluation of functions, by interpreting their basic blocks
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::str;
+
+use ir;
+use ir::runtime;
+use span::Span;
+use super::{SemanticError, SemanticErrorKind};
+use super::const_eval::{self, ConstValue};
+
+pub const MAX_STEPS: usize = 1_000_000; // statements and terminators
+pub const MAX_MEMORY: usize = 1 << 20; // live memory slots
+pub const MAX_DEPTH: usize = 256; // nested calls
+
+pub trait FunctionSource {
+    // None if the function is not defined in the program
+    fn get_function(&mut self,
+                    name: &str,
+                    ty: &ir::FunctionType)
+                    -> Result<Option<Rc<ir::Declaration>>, SemanticError>;
+}
+
+// memory is a list of allocations of slots, every scalar takes one slot
+#[derive(Debug, Clone, PartialEq)]
+enum Val {
+    Undef, // never stored
+    Zero, // from the zeroed allocations of the runtime
+    Unit,
+    Bool(bool),
+    Int(i32),
+    Double(f64),
+    Char(char),
+    Byte(u8),
+    Null,
+    Ptr(usize, i64), // (allocation, slot offset)
+    Function(String, ir::FunctionType),
+    Aggregate(Vec<Val>), // struct and str fields
+}
+
+struct Frame {
+    locals: HashMap<ir::LocalVarId, Val>,
+    temps: HashMap<usize, Val>,
+}
+
+struct Interpreter<'a, S: FunctionSource + 'a> {
+    source: &'a mut S,
+    span: Span,
+    allocations: Vec<Option<Vec<Val>>>, // None once freed
+    live_slots: usize,
+    steps: usize,
+    depth: usize,
+    literals: HashMap<Vec<u8>, usize>,
+    untyped: HashMap<usize, usize>, // size in bytes of the runtime allocations not yet cast
+}
+
+pub fn call_function<S: FunctionSource>(source: &mut S,
+                                        name: &str,
+                                        ty: &ir::FunctionType,
+                                        args: Vec<ConstValue>,
+                                        span: Span)
+                                        -> Result<ConstValue, SemanticError> {
+    let mut interpreter = Interpreter {
+        source: source,
+        span: span,
+        allocations: Vec::new(),
+        live_slots: 0,
+        steps: 0,
+        depth: 0,
+        literals: HashMap::new(),
+        untyped: HashMap::new(),
+    };
+    let args = args.into_iter().map(from_const).collect();
+    let result = interpreter.call(name, ty, args)?;
+    interpreter.to_const(result)
+}
+
+fn from_const(value: ConstValue) -> Val {
+    match value {
+        ConstValue::Bool(val) => Val::Bool(val),
+        ConstValue::Int(val) => Val::Int(val),
+        ConstValue::Double(val) => Val::Double(val),
+        ConstValue::Char(val) => Val::Char(val),
+        ConstValue::Byte(val) => Val::Byte(val),
+    }
+}
+
+fn slots(ty: &ir::Type) -> usize {
+    match *ty {
+        ir::Type::Unit => 0,
+        ir::Type::Str => 2,
+        ir::Type::Struct(ref st) => st.fields_ty.iter().map(|&(_, ref ty)| slots(ty)).sum(),
+        _ => 1,
+    }
+}
+
+fn pointee(ty: &ir::Type) -> &ir::Type {
+    match *ty {
+        ir::Type::Ptr(ref sub) | ir::Type::LValue(ref sub) => sub,
+        _ => unreachable!(),
+    }
+}
+
+fn fields_ty(ty: &ir::Type) -> Vec<ir::Type> {
+    let byte_ptr_ty = ir::Type::Ptr(Box::new(ir::Type::Byte));
+    match *ty {
+        ir::Type::Str => vec![byte_ptr_ty, ir::Type::Int],
+        ir::Type::Struct(ref st) => st.fields_ty.iter().map(|&(_, ref ty)| ty.clone()).collect(),
+        _ => unreachable!(),
+    }
+}
+
+impl<'a, S: FunctionSource> Interpreter<'a, S> {
+    fn error(&self, kind: SemanticErrorKind) -> SemanticError {
+        SemanticError {
+            kind: kind,
+            span: self.span,
+        }
+    }
+
+    fn invalid_access(&self) -> SemanticError {
+        self.error(SemanticErrorKind::ConstEvalInvalidMemoryAccess)
+    }
+
+    fn to_const(&self, value: Val) -> Result<ConstValue, SemanticError> {
+        match value {
+            Val::Bool(val) => Ok(ConstValue::Bool(val)),
+            Val::Int(val) => Ok(ConstValue::Int(val)),
+            Val::Double(val) => Ok(ConstValue::Double(val)),
+            Val::Char(val) => Ok(ConstValue::Char(val)),
+            Val::Byte(val) => Ok(ConstValue::Byte(val)),
+            _ => Err(self.invalid_access()), // read of uninitialized memory
+        }
+    }
+
+    fn step(&mut self) -> Result<(), SemanticError> {
+        self.steps += 1;
+        if self.steps > MAX_STEPS {
+            Err(self.error(SemanticErrorKind::ConstEvalStepLimit { limit: MAX_STEPS }))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn allocate(&mut self, size: usize, init: Val) -> Result<Val, SemanticError> {
+        self.live_slots += size;
+        if self.live_slots > MAX_MEMORY {
+            return Err(self.error(SemanticErrorKind::ConstEvalMemoryLimit { limit: MAX_MEMORY }));
+        }
+        self.allocations.push(Some(vec![init; size]));
+        Ok(Val::Ptr(self.allocations.len() - 1, 0))
+    }
+
+    // so that the accesses out of a `new` array are caught
+    fn retype(&mut self, alloc: usize, ty: &ir::Type) {
+        let size = match const_eval::size_of(ty) {
+            Some((size, _)) if size > 0 => size,
+            _ => return,
+        };
+        if let Some(bytes) = self.untyped.remove(&alloc) {
+            let len = bytes / size * slots(ty);
+            if let Some(ref mut slots) = self.allocations[alloc] {
+                self.live_slots -= slots.len() - len;
+                slots.truncate(len);
+            }
+        }
+    }
+
+    fn free(&mut self, ptr: Val) -> Result<(), SemanticError> {
+        match ptr {
+            Val::Null | Val::Zero => Ok(()),
+            Val::Ptr(alloc, 0) => {
+                match self.allocations[alloc].take() {
+                    Some(slots) => {
+                        self.live_slots -= slots.len();
+                        Ok(())
+                    }
+                    None => Err(self.invalid_access()),
+                }
+            }
+            _ => Err(self.invalid_access()),
+        }
+    }
+
+    fn slot(&mut self, ptr: &Val, offset: usize) -> Result<&mut Val, SemanticError> {
+        let invalid = self.invalid_access();
+        if let Val::Ptr(alloc, base) = *ptr {
+            let index = base + offset as i64;
+            if let Some(Some(ref mut slots)) = self.allocations.get_mut(alloc) {
+                if index >= 0 && (index as usize) < slots.len() {
+                    return Ok(&mut slots[index as usize]);
+                }
+            }
+        }
+        Err(invalid)
+    }
+
+    fn load(&mut self, ptr: &Val, offset: usize, ty: &ir::Type) -> Result<Val, SemanticError> {
+        match *ty {
+            ir::Type::Unit => Ok(Val::Unit),
+            ir::Type::Str | ir::Type::Struct(_) => {
+                let mut fields = Vec::new();
+                let mut field_offset = offset;
+                for field_ty in fields_ty(ty) {
+                    fields.push(self.load(ptr, field_offset, &field_ty)?);
+                    field_offset += slots(&field_ty);
+                }
+                Ok(Val::Aggregate(fields))
+            }
+            _ => {
+                let value = self.slot(ptr, offset)?.clone();
+                if value != Val::Zero {
+                    return Ok(value);
+                }
+                Ok(match *ty {
+                       ir::Type::Bool => Val::Bool(false),
+                       ir::Type::Int => Val::Int(0),
+                       ir::Type::Double => Val::Double(0.0),
+                       ir::Type::Char => Val::Char('\0'),
+                       ir::Type::Byte => Val::Byte(0),
+                       _ => Val::Null,
+                   })
+            }
+        }
+    }
+
+    fn store(&mut self,
+             ptr: &Val,
+             offset: usize,
+             ty: &ir::Type,
+             value: Val)
+             -> Result<(), SemanticError> {
+        match (ty, value) {
+            (&ir::Type::Unit, _) => Ok(()),
+            (&ir::Type::Str, Val::Aggregate(fields)) |
+            (&ir::Type::Struct(_), Val::Aggregate(fields)) => {
+                let mut field_offset = offset;
+                for (field_ty, field) in fields_ty(ty).into_iter().zip(fields) {
+                    self.store(ptr, field_offset, &field_ty, field)?;
+                    field_offset += slots(&field_ty);
+                }
+                Ok(())
+            }
+            (&ir::Type::Str, _) |
+            (&ir::Type::Struct(_), _) => Err(self.invalid_access()),
+            (_, value) => {
+                *self.slot(ptr, offset)? = value;
+                Ok(())
+            }
+        }
+    }
+
+    fn read_bytes(&mut self, data: &Val, len: Val) -> Result<Vec<u8>, SemanticError> {
+        let len = match len {
+            Val::Int(len) if len >= 0 => len as usize,
+            _ => return Err(self.invalid_access()),
+        };
+        let mut bytes = Vec::with_capacity(len);
+        for offset in 0..len {
+            match self.load(data, offset, &ir::Type::Byte)? {
+                Val::Byte(c) => bytes.push(c),
+                _ => return Err(self.invalid_access()),
+            }
+        }
+        Ok(bytes)
+    }
+
+    // NUL terminated, like the generated code
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<Val, SemanticError> {
+        let ptr = self.allocate(bytes.len() + 1, Val::Zero)?;
+        for (offset, &c) in bytes.iter().enumerate() {
+            self.store(&ptr, offset, &ir::Type::Byte, Val::Byte(c))?;
+        }
+        Ok(ptr)
+    }
+
+    fn call(&mut self,
+            name: &str,
+            ty: &ir::FunctionType,
+            args: Vec<Val>)
+            -> Result<Val, SemanticError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(self.error(SemanticErrorKind::ConstEvalRecursionLimit { limit: MAX_DEPTH }));
+        }
+
+        let func = self.source.get_function(name, ty)?;
+        match func.as_ref().map(|func| &**func) {
+            Some(&ir::Declaration::Function {
+                     ref locals,
+                     ref bbs,
+                     ..
+                 }) => {
+                self.depth += 1;
+                let result = self.call_function(locals, bbs, args);
+                self.depth -= 1;
+                result
+            }
+            _ => self.call_runtime(name, args),
+        }
+    }
+
+    fn call_function(&mut self,
+                     locals: &[ir::LocalVar],
+                     bbs: &[ir::BasicBlock],
+                     args: Vec<Val>)
+                     -> Result<Val, SemanticError> {
+        let mut frame = Frame {
+            locals: HashMap::new(),
+            temps: HashMap::new(),
+        };
+        for local in locals {
+            let ptr = self.allocate(slots(&local.ty) * local.size, Val::Undef)?;
+            if let Some(index) = local.param_index {
+                self.store(&ptr, 0, &local.ty, args[index].clone())?;
+            }
+            frame.locals.insert(local.id, ptr);
+        }
+
+        let result = self.run(&mut frame, bbs);
+        for (_, ptr) in frame.locals {
+            self.free(ptr)?;
+        }
+        result
+    }
+
+    fn run(&mut self, frame: &mut Frame, bbs: &[ir::BasicBlock]) -> Result<Val, SemanticError> {
+        let mut bb = &bbs[0];
+        loop {
+            for stmt in &bb.stmts {
+                self.step()?;
+                match *stmt {
+                    ir::Statement::LValueSet(ref dest, ref value) => {
+                        let ptr = frame.temps[&dest.id].clone();
+                        let value = frame.temps[&value.id].clone();
+                        self.store(&ptr, 0, pointee(&dest.ty), value)?;
+                    }
+                    ir::Statement::Assign(ref dest, ref expr) => {
+                        let value = self.eval(frame, expr)?;
+                        frame.temps.insert(dest.id, value);
+                    }
+                }
+            }
+
+            self.step()?;
+            let next = match bb.terminator {
+                ir::Terminator::Br(id) => id,
+                ir::Terminator::BrCond(ref cond, true_id, false_id) => {
+                    match frame.temps[&cond.id] {
+                        Val::Bool(true) => true_id,
+                        Val::Bool(false) => false_id,
+                        _ => return Err(self.invalid_access()),
+                    }
+                }
+                ir::Terminator::Ret(ref value) => {
+                    return Ok(frame.temps.get(&value.id).cloned().unwrap_or(Val::Unit))
+                }
+            };
+            bb = bbs.iter().find(|bb| bb.id == next).unwrap();
+        }
+    }
+
+    fn eval(&mut self, frame: &Frame, expr: &ir::Expression) -> Result<Val, SemanticError> {
+        let temp = |value: &ir::Value| frame.temps[&value.id].clone();
+        match *expr {
+            ir::Expression::LocalVarLoad(id) => Ok(frame.locals[&id].clone()),
+            ir::Expression::GlobalLoad(ref name, ref ty) => {
+                Ok(Val::Function(name.clone(), ty.clone()))
+            }
+            ir::Expression::LValueLoad(ref value) => {
+                self.load(&temp(value), 0, pointee(&value.ty))
+            }
+            ir::Expression::BinOp(ir::BinOpCode::PtrAdd, ref lhs, ref rhs) => {
+                match (temp(lhs), temp(rhs)) {
+                    (Val::Ptr(alloc, offset), Val::Int(index)) => {
+                        let size = slots(pointee(&lhs.ty)) as i64;
+                        Ok(Val::Ptr(alloc, offset + index as i64 * size))
+                    }
+                    _ => Err(self.invalid_access()),
+                }
+            }
+            ir::Expression::BinOp(op, ref lhs, ref rhs) => {
+                let lhs = self.to_const(temp(lhs))?;
+                let rhs = self.to_const(temp(rhs))?;
+                match const_eval::eval_binop(op, lhs, rhs) {
+                    Ok(value) => Ok(from_const(value)),
+                    Err(kind) => Err(self.error(kind)),
+                }
+            }
+            // lvalues and pointers are the same
+            ir::Expression::UnOp(ir::UnOpCode::AddressOf, ref value) |
+            ir::Expression::UnOp(ir::UnOpCode::PtrDeref, ref value) => Ok(temp(value)),
+            ir::Expression::UnOp(op, ref value) => {
+                let value = self.to_const(temp(value))?;
+                Ok(from_const(const_eval::eval_unop(op, value).unwrap()))
+            }
+            ir::Expression::CastOp(code, ref value) => {
+                let value = self.to_const(temp(value))?;
+                match const_eval::eval_cast(code, value) {
+                    Some(value) => Ok(from_const(value)),
+                    None => Err(const_eval::invalid_char(value, self.span)),
+                }
+            }
+            ir::Expression::FuncCall(ref func, ref args) => {
+                let args = args.iter().map(temp).collect();
+                match temp(func) {
+                    Val::Function(name, ty) => self.call(&name, &ty, args),
+                    _ => Err(self.invalid_access()),
+                }
+            }
+            ir::Expression::FieldAccess(ref value, index) => {
+                let offset: usize = fields_ty(pointee(&value.ty))[..index]
+                    .iter()
+                    .map(slots)
+                    .sum();
+                match temp(value) {
+                    Val::Ptr(alloc, base) => Ok(Val::Ptr(alloc, base + offset as i64)),
+                    _ => Err(self.invalid_access()),
+                }
+            }
+            ir::Expression::Literal(ir::Literal::Str(ref val)) => {
+                // literals are constant data, allocated once
+                let ptr = match self.literals.get(val).cloned() {
+                    Some(alloc) => Val::Ptr(alloc, 0),
+                    None => {
+                        let ptr = self.write_bytes(val)?;
+                        if let Val::Ptr(alloc, _) = ptr {
+                            self.literals.insert(val.clone(), alloc);
+                        }
+                        ptr
+                    }
+                };
+                Ok(Val::Aggregate(vec![ptr, Val::Int(val.len() as i32)]))
+            }
+            ir::Expression::Literal(ir::Literal::Unit) => Ok(Val::Unit),
+            ir::Expression::Literal(ref lit) => {
+                Ok(from_const(const_eval::from_literal(lit.clone()).unwrap()))
+            }
+            ir::Expression::SizeOf(ref ty) => {
+                Ok(Val::Int(const_eval::size_of(ty).unwrap().0 as i32))
+            }
+            ir::Expression::PtrCast(ref value, ref ty) => {
+                let value = temp(value);
+                if let Val::Ptr(alloc, 0) = value {
+                    self.retype(alloc, pointee(ty));
+                }
+                Ok(value)
+            }
+            ir::Expression::VaStart |
+            ir::Expression::VaArg(..) |
+            ir::Expression::VaEnd(_) => Err(self.error(SemanticErrorKind::NonConstantExpression)),
+        }
+    }
+
+    // the runtime helpers without side effects outside of the memory
+    fn call_runtime(&mut self, name: &str, mut args: Vec<Val>) -> Result<Val, SemanticError> {
+        if name == runtime::ALLOC {
+            let size = match (args[0].clone(), args[1].clone()) {
+                (Val::Int(count), Val::Int(size)) if count >= 0 && size >= 0 => {
+                    count.checked_mul(size)
+                }
+                _ => None,
+            };
+            match size {
+                Some(size) => {
+                    let ptr = self.allocate(size.max(1) as usize, Val::Zero)?;
+                    if let Val::Ptr(alloc, _) = ptr {
+                        self.untyped.insert(alloc, size as usize);
+                    }
+                    Ok(ptr)
+                }
+                _ => Err(self.invalid_access()),
+            }
+        } else if name == runtime::FREE {
+            self.free(args.remove(0))?;
+            Ok(Val::Unit)
+        } else if name == runtime::STR_AT {
+            match (args[1].clone(), args[2].clone()) {
+                (Val::Int(len), Val::Int(index)) if index >= 0 && index < len => {
+                    self.load(&args[0], index as usize, &ir::Type::Byte)
+                }
+                _ => Err(self.invalid_access()),
+            }
+        } else if name == runtime::STR_DECODE {
+            let bytes = self.read_bytes(&args[0], args[1].clone())?;
+            let index = match args[2] {
+                Val::Int(index) if index >= 0 && (index as usize) < bytes.len() => index as usize,
+                _ => return Err(self.invalid_access()),
+            };
+            // at most 4 bytes, the rest of the str is not needed
+            let end = bytes.len().min(index + 4);
+            let chars = match str::from_utf8(&bytes[index..end]) {
+                Ok(chars) => chars,
+                Err(err) => str::from_utf8(&bytes[index..index + err.valid_up_to()]).unwrap(),
+            };
+            match chars.chars().next() {
+                Some(c) => Ok(Val::Char(c)),
+                None => Err(self.invalid_access()),
+            }
+        } else if name == runtime::STR_COMPARE || name == runtime::STR_CONCAT {
+            let lhs = self.read_bytes(&args[0], args[1].clone())?;
+            let rhs = self.read_bytes(&args[2], args[3].clone())?;
+            if name == runtime::STR_COMPARE {
+                Ok(Val::Int(lhs.cmp(&rhs) as i32))
+            } else {
+                self.write_bytes(&[lhs, rhs].concat())
+            }
+        } else if name == runtime::STR_C_STR {
+            let bytes = self.read_bytes(&args[0], args[1].clone())?;
+            self.write_bytes(&bytes)
+        } else if name == runtime::ASSERT_FAIL {
+            let cond = self.read_bytes(&args[4], args[5].clone())?;
+            let message = self.read_bytes(&args[6], args[7].clone())?;
+            let message = if message.is_empty() {
+                None
+            } else {
+                Some(String::from_utf8_lossy(&message).into_owned())
+            };
+            Err(self.error(SemanticErrorKind::ConstEvalAssertFailed {
+                               cond: String::from_utf8_lossy(&cond).into_owned(),
+                               message: message,
+                           }))
+        } else {
+            let name = runtime::builtin_name(name).to_string();
+            Err(self.error(SemanticErrorKind::ConstEvalExternCall { name: name }))
+        }
+    }
+}
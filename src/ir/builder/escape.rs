@@ -0,0 +1,147 @@
+// the escape sequences of the string and char literals
+use std::char;
+use std::iter::Peekable;
+use std::str::{self, Chars};
+
+use span::Span;
+use super::{SemanticError, SemanticErrorKind};
+
+// the bytes of a string literal, the non-ASCII characters and `\u{...}` are
+// encoded in UTF-8, `\xNN` is a raw byte
+pub fn unescape(val: &str, span: Span) -> Result<Vec<u8>, SemanticError> {
+    unescape_bytes(val, false, span)
+}
+
+// a char literal is a single code point, `\xNN` must be ASCII
+pub fn unescape_char(val: &str, span: Span) -> Result<char, SemanticError> {
+    let bytes = unescape_bytes(val, true, span)?;
+    let chars: Vec<char> = str::from_utf8(&bytes).unwrap().chars().collect();
+    let kind = match chars.len() {
+        0 => SemanticErrorKind::EmptyCharLiteral,
+        1 => return Ok(chars[0]),
+        _ => SemanticErrorKind::MultipleCharLiteral,
+    };
+    Err(SemanticError {
+            kind: kind,
+            span: span,
+        })
+}
+
+// a byte literal is a single byte, so an ASCII character or a `\xNN` escape
+pub fn unescape_byte(val: &str, span: Span) -> Result<u8, SemanticError> {
+    let bytes = unescape(val, span)?;
+    let kind = match bytes.len() {
+        0 => SemanticErrorKind::EmptyCharLiteral,
+        1 => return Ok(bytes[0]),
+        _ => {
+            match str::from_utf8(&bytes).map(|s| s.chars().collect::<Vec<_>>()) {
+                Ok(ref chars) if chars.len() == 1 => {
+                    SemanticErrorKind::NonAsciiByteLiteral { c: chars[0] }
+                }
+                _ => SemanticErrorKind::MultipleCharLiteral,
+            }
+        }
+    };
+    Err(SemanticError {
+            kind: kind,
+            span: span,
+        })
+}
+
+// the result is valid UTF-8 when `ascii_hex` is set
+fn unescape_bytes(val: &str, ascii_hex: bool, span: Span) -> Result<Vec<u8>, SemanticError> {
+    let mut bytes = Vec::with_capacity(val.len());
+    let mut chars = val.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            push_char(&mut bytes, c);
+            continue;
+        }
+
+        let c = chars.next().unwrap(); // the lexer never ends a literal after a '\'
+        match c {
+            '\'' | '\"' | '\\' => bytes.push(c as u8),
+            'a' => bytes.push(b'\x07'),
+            'b' => bytes.push(b'\x08'),
+            'f' => bytes.push(b'\x0c'),
+            'n' => bytes.push(b'\n'),
+            'r' => bytes.push(b'\r'),
+            't' => bytes.push(b'\t'),
+            'v' => bytes.push(b'\x0b'),
+            '0' => bytes.push(b'\0'),
+            'x' => {
+                let byte = unescape_hex(&mut chars, span)?;
+                if ascii_hex && byte > 0x7f {
+                    return Err(SemanticError {
+                                   kind: SemanticErrorKind::NonAsciiHexEscape,
+                                   span: span,
+                               });
+                }
+                bytes.push(byte);
+            }
+            'u' => push_char(&mut bytes, unescape_unicode(&mut chars, span)?),
+            // a line continuation, the leading whitespaces of the next line are skipped
+            '\n' | '\r' => {
+                while chars.peek().map(|c| c.is_whitespace()).unwrap_or(false) {
+                    chars.next();
+                }
+            }
+            _ => {
+                return Err(SemanticError {
+                               kind: SemanticErrorKind::InvalidEscapeChar { c: c },
+                               span: span,
+                           })
+            }
+        }
+    }
+    Ok(bytes)
+}
+
+fn push_char(bytes: &mut Vec<u8>, c: char) {
+    let mut buffer = String::with_capacity(4);
+    buffer.push(c);
+    bytes.extend(buffer.bytes());
+}
+
+// `\xNN`, exactly two digits
+fn unescape_hex(chars: &mut Peekable<Chars>, span: Span) -> Result<u8, SemanticError> {
+    let digits: String = chars.clone().take(2).collect();
+    match u8::from_str_radix(&digits, 16) {
+        Ok(byte) if digits.len() == 2 && digits.chars().all(|c| c.is_digit(16)) => {
+            chars.next();
+            chars.next();
+            Ok(byte)
+        }
+        _ => {
+            Err(SemanticError {
+                    kind: SemanticErrorKind::InvalidHexEscape,
+                    span: span,
+                })
+        }
+    }
+}
+
+// `\u{XXXX}`, from one to six digits
+fn unescape_unicode(chars: &mut Peekable<Chars>, span: Span) -> Result<char, SemanticError> {
+    let error = SemanticError {
+        kind: SemanticErrorKind::InvalidUnicodeEscape,
+        span: span,
+    };
+
+    if chars.next() != Some('{') {
+        return Err(error);
+    }
+    let mut digits = String::new();
+    loop {
+        match chars.next() {
+            Some('}') => break,
+            Some(c) if c.is_digit(16) && digits.len() < 6 => digits.push(c),
+            _ => return Err(error),
+        }
+    }
+
+    u32::from_str_radix(&digits, 16)
+        .ok()
+        .and_then(char::from_u32)
+        .ok_or(error)
+}
@@ -0,0 +1,142 @@
+#[derive(Debug, Clone, PartialEq)]
+pub enum PrintPiece {
+    Text(Vec<u8>),
+    Placeholder,
+}
+
+// `{}` is the only placeholder, `{{` and `}}` are escaped braces
+// Err contains the invalid placeholder
+pub fn parse_print_format(format: &[u8]) -> Result<Vec<PrintPiece>, String> {
+    let mut pieces = Vec::new();
+    let mut text = Vec::new();
+    let mut index = 0;
+
+    while index < format.len() {
+        let c = format[index];
+        let next = format.get(index + 1).cloned();
+        if (c == b'{' && next == Some(b'{')) || (c == b'}' && next == Some(b'}')) {
+            text.push(c);
+            index += 2;
+        } else if c == b'{' && next == Some(b'}') {
+            if !text.is_empty() {
+                pieces.push(PrintPiece::Text(text));
+                text = Vec::new();
+            }
+            pieces.push(PrintPiece::Placeholder);
+            index += 2;
+        } else if c == b'{' {
+            // up to the closing brace, if any
+            let end = format[index..]
+                .iter()
+                .position(|&c| c == b'}')
+                .map(|pos| index + pos + 1)
+                .unwrap_or_else(|| format.len());
+            return Err(String::from_utf8_lossy(&format[index..end]).into_owned());
+        } else if c == b'}' {
+            return Err("}".to_string());
+        } else {
+            text.push(c);
+            index += 1;
+        }
+    }
+
+    if !text.is_empty() {
+        pieces.push(PrintPiece::Text(text));
+    }
+    Ok(pieces)
+}
+
+// what a printf conversion reads from the variadic arguments
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PrintfArg {
+    Int,
+    Char,
+    Double,
+    CString,
+    Pointer,
+    IntPointer,
+    Unsupported, // no matching Elang type, like `%ld`
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrintfSpec {
+    pub spec: String,
+    pub arg: PrintfArg,
+}
+
+// one spec per consumed argument, `*` width and precision included
+// Err contains an invalid specification
+pub fn parse_printf_format(format: &[u8]) -> Result<Vec<PrintfSpec>, String> {
+    let mut specs = Vec::new();
+    let mut index = 0;
+
+    while index < format.len() {
+        if format[index] != b'%' {
+            index += 1;
+            continue;
+        }
+
+        let start = index;
+        index += 1;
+        if format.get(index) == Some(&b'%') {
+            index += 1;
+            continue;
+        }
+
+        let mut stars = 0;
+        while index < format.len() && b"-+ #0".contains(&format[index]) {
+            index += 1;
+        }
+        for part in 0..2 {
+            if part == 1 {
+                if format.get(index) != Some(&b'.') {
+                    break;
+                }
+                index += 1;
+            }
+            if format.get(index) == Some(&b'*') {
+                stars += 1;
+                index += 1;
+            }
+            while index < format.len() && format[index].is_ascii_digit() {
+                index += 1;
+            }
+        }
+
+        let length_start = index;
+        while index < format.len() && b"hlLqjzt".contains(&format[index]) {
+            index += 1;
+        }
+        let length = &format[length_start..index];
+
+        let conversion = format.get(index).cloned();
+        index += 1;
+        let spec = String::from_utf8_lossy(&format[start..index.min(format.len())]).into_owned();
+
+        let arg = match (length, conversion) {
+            (b"", Some(c)) | (b"h", Some(c)) | (b"hh", Some(c)) if b"diouxX".contains(&c) => {
+                PrintfArg::Int
+            }
+            (b"", Some(b'c')) => PrintfArg::Char,
+            (b"", Some(c)) | (b"l", Some(c)) if b"fFeEgGaA".contains(&c) => PrintfArg::Double,
+            (b"", Some(b's')) => PrintfArg::CString,
+            (b"", Some(b'p')) => PrintfArg::Pointer,
+            (b"", Some(b'n')) => PrintfArg::IntPointer,
+            (_, Some(c)) if b"diouxXcfFeEgGaAspn".contains(&c) => PrintfArg::Unsupported,
+            _ => return Err(spec),
+        };
+
+        for _ in 0..stars {
+            specs.push(PrintfSpec {
+                           spec: spec.clone(),
+                           arg: PrintfArg::Int,
+                       });
+        }
+        specs.push(PrintfSpec {
+                       spec: spec,
+                       arg: arg,
+                   });
+    }
+
+    Ok(specs)
+}
@@ -1,9 +1,13 @@
 use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
 
+use ast;
 use ir;
 use ir::{GlobalTable, SymbolTable};
-use ir::builder::{SemanticError, SemanticErrorKind};
-use span::Span;
+use ir::builder::{ConstSources, SemanticError, SemanticErrorKind, SemanticWarning};
+use pipeline::CompileOptions;
+use source_manager::SourceManager;
+use span::{Spanned, Span};
 
 #[derive(Debug, Clone)]
 enum Item {
@@ -11,12 +15,39 @@ enum Item {
     TerminatorAndLabel(Option<ir::Terminator>, ir::BasicBlockId), // None if fallthrough
 }
 
+#[derive(Debug, Clone)]
+pub struct LoopInfo {
+    pub label: Option<String>,
+    pub continue_label: ir::BasicBlockId,
+    pub break_label: ir::BasicBlockId,
+    pub accepts_value: bool, // only `loop` can be broken with a value
+    pub result: Option<(ir::Type, Option<ir::LocalVarId>)>, // no local for ()
+    pub defer_depth: usize, // defer scopes opened before the loop
+}
+
+// the symbol table is the one seen by the `defer` statement
+#[derive(Debug, Clone)]
+pub struct Deferred<'a> {
+    pub expr: Spanned<ast::Expression>,
+    pub symbol_table: SymbolTable<'a>,
+}
+
 #[derive(Debug)]
 pub struct FunctionBuilder<'a> {
     pub name: String,
     pub ty: ir::FunctionType,
     pub symbol_table: SymbolTable<'a>,
-    pub current_loop_info: Option<(ir::BasicBlockId, ir::BasicBlockId)>, // (continue, break)
+    pub options: &'a CompileOptions,
+    pub source_manager: &'a SourceManager,
+    pub loop_infos: Vec<LoopInfo>, // innermost loop last
+    pub defer_scopes: Vec<Vec<Deferred<'a>>>, // one per compound statement
+    pub in_defer: bool,
+    pub warnings: Vec<SemanticWarning>,
+    pub eq_types: Vec<(ir::StructType, Span)>, // compared with ==, see build_struct_eq
+    pub stack_arrays: HashSet<ir::LocalVarId>, // bound to an array literal, see modified_variable
+    pub owned_strs: HashSet<ir::LocalVarId>, // bound to a concatenation, see is_owned_str
+    pub const_sources: ConstSources<'a>,
+    pub const_functions: HashMap<(String, ir::FunctionType), Rc<ir::Declaration>>,
     locals: Vec<ir::LocalVar>,
     items: Vec<Item>,
     local_counter: usize,
@@ -25,12 +56,28 @@ pub struct FunctionBuilder<'a> {
 }
 
 impl<'a> FunctionBuilder<'a> {
-    pub fn new(name: String, ty: ir::FunctionType, globals_table: &'a GlobalTable) -> Self {
+    pub fn new(name: String,
+               ty: ir::FunctionType,
+               const_sources: ConstSources<'a>,
+               globals_table: &'a GlobalTable,
+               options: &'a CompileOptions,
+               source_manager: &'a SourceManager)
+               -> Self {
         FunctionBuilder {
             name: name,
             ty: ty,
             symbol_table: SymbolTable::new(globals_table),
-            current_loop_info: None,
+            options: options,
+            source_manager: source_manager,
+            loop_infos: Vec::new(),
+            defer_scopes: Vec::new(),
+            in_defer: false,
+            warnings: Vec::new(),
+            eq_types: Vec::new(),
+            stack_arrays: HashSet::new(),
+            owned_strs: HashSet::new(),
+            const_sources: const_sources,
+            const_functions: HashMap::new(),
             locals: Vec::new(),
             items: Vec::new(),
             local_counter: 0,
@@ -39,7 +86,10 @@ impl<'a> FunctionBuilder<'a> {
         }
     }
 
-    pub fn into_function(self, span: Span) -> Result<ir::Declaration, SemanticError> {
+    pub fn into_function(self,
+                         attributes: Vec<ir::Attribute>,
+                         span: Span)
+                         -> Result<ir::Declaration, SemanticError> {
         #[derive(Clone)]
         enum PanicTerminator {
             Real(ir::Terminator),
@@ -144,6 +194,7 @@ impl<'a> FunctionBuilder<'a> {
                ty: self.ty,
                locals: self.locals,
                bbs: real_bbs,
+               attributes: attributes,
            })
     }
 
@@ -168,13 +219,107 @@ impl<'a> FunctionBuilder<'a> {
         self.push_terminator_label(terminator, label);
     }
 
+    pub fn push_loop_info(&mut self,
+                          label: Option<String>,
+                          continue_label: ir::BasicBlockId,
+                          break_label: ir::BasicBlockId,
+                          accepts_value: bool) {
+        self.loop_infos
+            .push(LoopInfo {
+                      label: label,
+                      continue_label: continue_label,
+                      break_label: break_label,
+                      accepts_value: accepts_value,
+                      result: None,
+                      defer_depth: self.defer_scopes.len(),
+                  });
+    }
+
+    pub fn pop_loop_info(&mut self) -> LoopInfo {
+        self.loop_infos.pop().unwrap()
+    }
+
+    // the innermost loop if label is None
+    pub fn find_loop_index(&self, label: &Option<String>) -> Option<usize> {
+        if let Some(ref label) = *label {
+            self.loop_infos
+                .iter()
+                .rposition(|info| info.label.as_ref() == Some(label))
+        } else if self.loop_infos.is_empty() {
+            None
+        } else {
+            Some(self.loop_infos.len() - 1)
+        }
+    }
+
+    pub fn push_defer_scope(&mut self) {
+        self.defer_scopes.push(Vec::new());
+    }
+
+    // in execution order
+    pub fn pop_defer_scope(&mut self) -> Vec<Deferred<'a>> {
+        let mut deferred = self.defer_scopes.pop().unwrap();
+        deferred.reverse();
+        deferred
+    }
+
+    pub fn push_deferred(&mut self, expr: Spanned<ast::Expression>) {
+        let deferred = Deferred {
+            expr: expr,
+            symbol_table: self.symbol_table.clone(),
+        };
+        self.defer_scopes.last_mut().unwrap().push(deferred);
+    }
+
+    // what a jump out of the scopes opened after depth must run, in execution order
+    pub fn deferred_since(&self, depth: usize) -> Vec<Deferred<'a>> {
+        self.defer_scopes[depth..]
+            .iter()
+            .rev()
+            .flat_map(|scope| scope.iter().rev().cloned())
+            .collect()
+    }
+
     pub fn push_statement(&mut self, stmt: ir::Statement) {
         self.items.push(Item::Statement(stmt));
     }
 
-    pub fn register_param(&mut self, name: String, ty: ir::Type, param_index: Option<usize>) -> bool {
-        let res = self.symbol_table
-            .register_local(name, ty.clone(), ir::LocalVarId(self.local_counter));
+    pub fn code_len(&self) -> usize {
+        self.items.len()
+    }
+
+    // drops the code pushed since code_len returned len, the expressions are only type-checked
+    pub fn discard_code_since(&mut self, len: usize) {
+        self.items.truncate(len);
+    }
+
+    // the parameters are immutable
+    pub fn register_param(&mut self,
+                          name: Spanned<String>,
+                          ty: ir::Type,
+                          param_index: Option<usize>)
+                          -> bool {
+        self.register_named(name.inner, ty, param_index, false, name.span)
+    }
+
+    pub fn register_local_variable(&mut self,
+                                   name: String,
+                                   ty: ir::Type,
+                                   mutable: bool,
+                                   span: Span)
+                                   -> bool {
+        self.register_named(name, ty, None, mutable, span)
+    }
+
+    fn register_named(&mut self,
+                      name: String,
+                      ty: ir::Type,
+                      param_index: Option<usize>,
+                      mutable: bool,
+                      span: Span)
+                      -> bool {
+        let id = ir::LocalVarId(self.local_counter);
+        let res = self.symbol_table.register_local(name, ty.clone(), id, mutable, span);
         self.locals
             .push(ir::LocalVar {
                       id: ir::LocalVarId(self.local_counter),
@@ -186,10 +331,6 @@ impl<'a> FunctionBuilder<'a> {
         res
     }
 
-    pub fn register_local_variable(&mut self, name: String, ty: ir::Type) -> bool {
-        self.register_param(name, ty, None)
-    }
-
     pub fn register_local_logical(&mut self) -> ir::LocalVarId {
         self.register_local_unnamed(ir::Type::Bool)
     }
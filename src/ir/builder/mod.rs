@@ -1,60 +1,294 @@
 mod typecheck_defs;
 mod function_builder;
 mod semantic_error;
+mod format;
+mod const_eval;
+mod ctfe;
+mod escape;
+mod call;
+
+use std::collections::HashMap;
+use std::mem;
+use std::rc::Rc;
 
 use rayon::prelude::*;
 use ir;
 use ast;
 use span::{Spanned, Span};
-use ir::GlobalTable;
-use self::function_builder::FunctionBuilder;
+use ir::{GlobalTable, SymbolTable};
+use ir::runtime;
+use pipeline::CompileOptions;
+use source_manager::SourceManager;
+use self::function_builder::{Deferred, FunctionBuilder};
+use self::format::PrintPiece;
 pub use self::semantic_error::{SemanticError, SemanticErrorKind};
+pub use self::semantic_error::{SemanticWarning, SemanticWarningKind};
+
+// the field-wise comparison of a struct type, generated once per compared type
+const STRUCT_EQ: &'static str = "__elang_struct_eq";
+
+type BuiltDeclaration = (ir::Declaration, Vec<SemanticWarning>, Vec<(ir::StructType, Span)>);
 
 pub fn build_translation_unit(tu: ast::TranslationUnit,
                               mut declarations: Vec<ir::Declaration>,
-                              globals_table: &mut GlobalTable)
-                              -> Result<ir::TranslationUnit, SemanticError> {
+                              globals_table: &mut GlobalTable,
+                              options: &CompileOptions,
+                              source_manager: &SourceManager)
+                              -> Result<(ir::TranslationUnit, Vec<SemanticWarning>),
+                                        SemanticError> {
     let mut predeclarations = Vec::with_capacity(tu.declarations.len());
+    let mut const_predeclarations = Vec::new();
     for decl in tu.declarations {
-        if let Some(predecl) = register_declaration(decl, globals_table)? {
-            predeclarations.push(predecl);
+        match register_declaration(decl, globals_table)? {
+            Some(predecl @ PreDeclaration::StaticAssert { .. }) |
+            Some(predecl @ PreDeclaration::Const { .. }) => const_predeclarations.push(predecl),
+            Some(predecl) => predeclarations.push(predecl),
+            None => {}
+        }
+    }
+
+    // once every type is registered, in declaration order
+    let mut const_functions = HashMap::new();
+    let sources = ConstSources {
+        predeclarations: &predeclarations,
+        declarations: &declarations,
+        building: Vec::new(),
+    };
+    for predecl in const_predeclarations {
+        let span = match predecl {
+            PreDeclaration::StaticAssert { ref cond, .. } => cond.span,
+            PreDeclaration::Const { span, .. } => span,
+            _ => unreachable!(),
+        };
+        let mut calls = ConstFunctions {
+            sources: &sources,
+            globals_table: globals_table,
+            options: options,
+            source_manager: source_manager,
+            cache: &mut const_functions,
+            span: span,
+        };
+        let symbol_table = SymbolTable::new(calls.globals_table);
+        let value = match predecl {
+            PreDeclaration::StaticAssert { cond, message } => {
+                build_static_assert(&symbol_table, &mut calls, cond, message)?;
+                None
+            }
+            PreDeclaration::Const { name, ty, expr, .. } => {
+                Some((name, build_const(&symbol_table, &mut calls, ty, expr)?))
+            }
+            _ => unreachable!(),
+        };
+
+        if let Some((name, value)) = value {
+            if !globals_table.register_const(name.clone(), value.to_literal()) {
+                return Err(SemanticError {
+                               kind: SemanticErrorKind::ConstAlreadyDefined { name: name },
+                               span: span,
+                           });
+            }
         }
     }
 
-    declarations.reserve(predeclarations.len());
+    check_export_names(&predeclarations, &declarations)?;
+
     let rdecls: Vec<_> = predeclarations
-        .into_par_iter()
-        .map(|predecl| build_predeclaration(predecl, globals_table))
+        .par_iter()
+        .map(|predecl| {
+                 build_predeclaration(predecl.clone(),
+                                      &sources,
+                                      globals_table,
+                                      options,
+                                      source_manager)
+             })
         .collect();
+    let mut built = Vec::with_capacity(rdecls.len());
+    let mut warnings = Vec::new();
+    let mut eq_types = Vec::new();
     for rdecl in rdecls {
-        declarations.push(rdecl?);
+        let (decl, mut decl_warnings, mut decl_eq_types) = rdecl?;
+        built.push(decl);
+        warnings.append(&mut decl_warnings);
+        eq_types.append(&mut decl_eq_types);
+    }
+
+    // a helper compares its nested structs with their own helpers,
+    // the imported modules may already define some of them
+    while let Some((st, span)) = eq_types.pop() {
+        let ty = struct_eq_type(st.clone());
+        let defined = declarations
+            .iter()
+            .chain(&built)
+            .any(|decl| match *decl {
+                     ir::Declaration::Function {
+                         ref name,
+                         ty: ref func_ty,
+                         ..
+                     } => name == STRUCT_EQ && *func_ty == ty,
+                     _ => false,
+                 });
+        if defined {
+            continue;
+        }
+
+        let predecl = struct_eq_predeclaration(st, span);
+        let (decl, mut decl_warnings, mut decl_eq_types) =
+            build_predeclaration(predecl, &sources, globals_table, options, source_manager)?;
+        built.push(decl);
+        warnings.append(&mut decl_warnings);
+        eq_types.append(&mut decl_eq_types);
     }
 
-    Ok(ir::TranslationUnit { declarations: declarations })
+    declarations.append(&mut built);
+    Ok((ir::TranslationUnit { declarations: declarations }, warnings))
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum PreDeclaration {
-    ExternFunction { name: String, ty: ir::FunctionType },
+    ExternFunction {
+        name: String,
+        ty: ir::FunctionType,
+        attributes: Vec<ir::Attribute>,
+    },
     Function {
         name: String,
         param_names: Vec<Spanned<String>>,
         ty: ir::FunctionType,
         stmt: Spanned<ast::CompoundStatement>,
+        attributes: Vec<ir::Attribute>,
+        span: Span,
+    },
+    StaticAssert {
+        cond: Spanned<ast::Expression>,
+        message: Option<String>,
+    },
+    Const {
+        name: String,
+        ty: Option<Spanned<ast::ParseType>>,
+        expr: Spanned<ast::Expression>,
         span: Span,
     },
 }
 
-fn register_declaration(decl: Spanned<ast::Declaration>,
+// what the constant expressions can call, the global ones and the ones of the functions
+#[derive(Debug, Clone)]
+pub struct ConstSources<'a> {
+    predeclarations: &'a [PreDeclaration],
+    declarations: &'a [ir::Declaration],
+    building: Vec<(String, ir::FunctionType)>, // built for a constant evaluation, innermost last
+}
+
+// builds the functions called by the constants when they are first needed,
+// every error of the evaluation is reported at the constant
+struct ConstFunctions<'a> {
+    sources: &'a ConstSources<'a>,
+    globals_table: &'a GlobalTable,
+    options: &'a CompileOptions,
+    source_manager: &'a SourceManager,
+    cache: &'a mut HashMap<(String, ir::FunctionType), Rc<ir::Declaration>>,
+    span: Span,
+}
+
+impl<'a> ctfe::FunctionSource for ConstFunctions<'a> {
+    fn get_function(&mut self,
+                    name: &str,
+                    ty: &ir::FunctionType)
+                    -> Result<Option<Rc<ir::Declaration>>, SemanticError> {
+        let key = (name.to_string(), ty.clone());
+        if let Some(func) = self.cache.get(&key) {
+            return Ok(Some(func.clone()));
+        }
+
+        if self.sources.building.contains(&key) {
+            return Err(SemanticError {
+                           kind: SemanticErrorKind::ConstFunctionCycle { name: key.0 },
+                           span: self.span,
+                       });
+        }
+
+        let predecl = self.sources
+            .predeclarations
+            .iter()
+            .find(|predecl| match **predecl {
+                      PreDeclaration::Function {
+                          name: ref func_name,
+                          ty: ref func_ty,
+                          ..
+                      } => func_name == name && func_ty == ty,
+                      _ => false,
+                  })
+            .cloned();
+        let imported = self.sources
+            .declarations
+            .iter()
+            .find(|decl| match **decl {
+                      ir::Declaration::Function {
+                          name: ref func_name,
+                          ty: ref func_ty,
+                          ..
+                      } => func_name == name && func_ty == ty,
+                      _ => false,
+                  });
+        // the struct comparisons are only generated after the functions
+        let predecl = match ty.params_ty.first() {
+            Some(&ir::Type::Struct(ref st)) if predecl.is_none() && imported.is_none() &&
+                                              name == STRUCT_EQ => {
+                Some(struct_eq_predeclaration(st.clone(), self.span))
+            }
+            _ => predecl,
+        };
+
+        let func = if let Some(predecl) = predecl {
+            let mut sources = self.sources.clone();
+            sources.building.push(key.clone());
+            build_predeclaration(predecl,
+                                 &sources,
+                                 self.globals_table,
+                                 self.options,
+                                 self.source_manager)?
+                    .0
+        } else {
+            match imported {
+                Some(decl) => decl.clone(),
+                None => return Ok(None), // extern
+            }
+        };
+
+        let func = Rc::new(func);
+        self.cache.insert(key, func.clone());
+        Ok(Some(func))
+    }
+}
+
+impl<'a> const_eval::CallEvaluator for ConstFunctions<'a> {
+    fn call(&mut self,
+            name: &str,
+            ty: &ir::FunctionType,
+            args: Vec<const_eval::ConstValue>,
+            _: Span)
+            -> Result<const_eval::ConstValue, SemanticError> {
+        let span = self.span;
+        ctfe::call_function(self, name, ty, args, span)
+    }
+}
+
+fn register_declaration(mut decl: Spanned<ast::Declaration>,
                         globals_table: &mut GlobalTable)
                         -> Result<Option<PreDeclaration>, SemanticError> {
+    // the doc comments are only kept in the AST, see ast::Declaration::doc
+    decl.inner
+        .attributes_mut()
+        .retain(|attribute| attribute.inner.name() != "doc");
+
     match decl.inner {
         ast::Declaration::ExternFunction {
             name,
             params,
             variadic,
             return_ty,
+            attributes,
         } => {
+            let attributes = build_function_attributes(attributes, true)?;
             let return_ty = build_type(return_ty, globals_table)?;
 
             let mut param_types = Vec::with_capacity(params.len());
@@ -68,26 +302,39 @@ fn register_declaration(decl: Spanned<ast::Declaration>,
                 variadic: variadic,
             };
 
-            if !globals_table.register_global(name.clone(), ir::Type::Function(ty.clone())) {
+            let params = ty.params_ty.iter().map(|_| ir::Param::unnamed()).collect();
+            if !globals_table.register_global(name.clone(), ty.clone(), params) {
                 return Err(SemanticError {
                                kind: SemanticErrorKind::FunctionAlreadyDefined { name: name },
                                span: decl.span,
                            });
             }
 
-            Ok(Some(PreDeclaration::ExternFunction { name: name, ty: ty }))
+            Ok(Some(PreDeclaration::ExternFunction {
+                        name: name,
+                        ty: ty,
+                        attributes: attributes,
+                    }))
         }
         ast::Declaration::Function {
             name,
             params,
+            variadic,
             return_ty,
             stmt,
+            attributes,
         } => {
+            let attributes = build_function_attributes(attributes, false)?;
             let return_ty = build_type(return_ty, globals_table)?;
 
             let mut param_names = Vec::with_capacity(params.len());
             let mut param_types = Vec::with_capacity(params.len());
-            for (name, ty) in params {
+            let mut call_params = Vec::with_capacity(params.len());
+            for (name, ty, default) in params {
+                call_params.push(ir::Param {
+                                     name: Some(name.inner.clone()),
+                                     default: default,
+                                 });
                 param_names.push(name);
                 param_types.push(build_type(ty, globals_table)?);
             }
@@ -95,10 +342,28 @@ fn register_declaration(decl: Spanned<ast::Declaration>,
             let ty = ir::FunctionType {
                 return_ty: Box::new(return_ty),
                 params_ty: param_types,
-                variadic: false,
+                variadic: variadic,
             };
 
-            if !globals_table.register_global(name.clone(), ir::Type::Function(ty.clone())) {
+            let overload = ir::Overload {
+                ty: ty.clone(),
+                params: call_params,
+            };
+            let other_ty = globals_table
+                .conflicting_overload(&name, &overload)
+                .map(|other| other.ty.clone());
+            if let Some(other_ty) = other_ty {
+                if other_ty.params_ty != ty.params_ty {
+                    return Err(SemanticError {
+                                   kind: SemanticErrorKind::AmbiguousDefaultValues {
+                                       name: name,
+                                       other: other_ty,
+                                   },
+                                   span: decl.span,
+                               });
+                }
+            }
+            if !globals_table.register_global(name.clone(), ty.clone(), overload.params) {
                 return Err(SemanticError {
                                kind: SemanticErrorKind::FunctionAlreadyDefined { name: name },
                                span: decl.span,
@@ -110,10 +375,52 @@ fn register_declaration(decl: Spanned<ast::Declaration>,
                         param_names: param_names,
                         ty: ty,
                         stmt: stmt,
+                        attributes: attributes,
+                        span: decl.span,
+                    }))
+        }
+        ast::Declaration::StaticAssert {
+            cond,
+            message,
+            attributes,
+        } => {
+            if let Some(attribute) = attributes.into_iter().next() {
+                return Err(attribute_error(attribute));
+            }
+            Ok(Some(PreDeclaration::StaticAssert {
+                        cond: cond,
+                        message: message,
+                    }))
+        }
+        ast::Declaration::Const {
+            name,
+            ty,
+            expr,
+            attributes,
+        } => {
+            if let Some(attribute) = attributes.into_iter().next() {
+                return Err(attribute_error(attribute));
+            }
+            Ok(Some(PreDeclaration::Const {
+                        name: name,
+                        ty: ty,
+                        expr: expr,
                         span: decl.span,
                     }))
         }
-        ast::Declaration::Struct { name, fields } => {
+        ast::Declaration::Struct {
+            name,
+            fields,
+            attributes,
+        } => {
+            let mut packed = false;
+            for attribute in attributes {
+                match attribute.inner {
+                    ast::Meta::Word(ref name) if name == "packed" => packed = true,
+                    _ => return Err(attribute_error(attribute)),
+                }
+            }
+
             let mut field_names = Vec::with_capacity(fields.len());
             let mut final_fields = Vec::with_capacity(fields.len());
             for (name, ty) in fields {
@@ -130,7 +437,10 @@ fn register_declaration(decl: Spanned<ast::Declaration>,
                 field_names.push(name.inner);
             }
 
-            let struct_ty = ir::Type::Struct(ir::StructType { fields_ty: final_fields });
+            let struct_ty = ir::Type::Struct(ir::StructType {
+                                                 fields_ty: final_fields,
+                                                 packed: packed,
+                                             });
 
             if globals_table.register_ty(name.clone(), struct_ty) {
                 Ok(None)
@@ -144,24 +454,210 @@ fn register_declaration(decl: Spanned<ast::Declaration>,
     }
 }
 
+// an export_name can't be the symbol of another function, even an imported one
+fn check_export_names(predeclarations: &[PreDeclaration],
+                      declarations: &[ir::Declaration])
+                      -> Result<(), SemanticError> {
+    fn is_export(attributes: &[ir::Attribute]) -> bool {
+        attributes
+            .iter()
+            .any(|attribute| match *attribute {
+                     ir::Attribute::ExportName(_) => true,
+                     _ => false,
+                 })
+    }
+
+    let runtime_declarations = runtime::declarations();
+    let mut symbols = Vec::new(); // (symbol, exported, span of a function of this module)
+    for predecl in predeclarations {
+        match *predecl {
+            PreDeclaration::ExternFunction {
+                ref name,
+                ref attributes,
+                ..
+            } => symbols.push((ir::Attribute::symbol(name, attributes), false, None)),
+            PreDeclaration::Function {
+                ref name,
+                ref attributes,
+                span,
+                ..
+            } => {
+                symbols.push((ir::Attribute::symbol(name, attributes),
+                              is_export(attributes),
+                              Some(span)))
+            }
+            _ => {}
+        }
+    }
+    for decl in declarations.iter().chain(&runtime_declarations) {
+        match *decl {
+            ir::Declaration::ExternFunction {
+                ref name,
+                ref attributes,
+                ..
+            } |
+            ir::Declaration::Function {
+                ref name,
+                ref attributes,
+                ..
+            } => {
+                symbols.push((ir::Attribute::symbol(name, attributes), is_export(attributes), None))
+            }
+        }
+    }
+
+    let collision = |index: usize| {
+        let (symbol, exported, _) = symbols[index];
+        symbols
+            .iter()
+            .enumerate()
+            .any(|(other_index, &(other_symbol, other_exported, _))| {
+                     other_index != index && other_symbol == symbol && (exported || other_exported)
+                 })
+    };
+    // reported at the export_name if it is in this module
+    let mut colliding: Vec<_> = (0..symbols.len())
+        .filter(|&index| symbols[index].2.is_some() && collision(index))
+        .collect();
+    colliding.sort_by_key(|&index| !symbols[index].1);
+    match colliding.first() {
+        Some(&index) => {
+            Err(SemanticError {
+                    kind: SemanticErrorKind::SymbolAlreadyDefined {
+                        symbol: symbols[index].0.to_string(),
+                    },
+                    span: symbols[index].2.unwrap(),
+                })
+        }
+        None => Ok(()),
+    }
+}
+
+fn build_function_attributes(attributes: Vec<Spanned<ast::Meta>>,
+                             is_extern: bool)
+                             -> Result<Vec<ir::Attribute>, SemanticError> {
+    let mut names: Vec<String> = Vec::with_capacity(attributes.len());
+    let mut result = Vec::with_capacity(attributes.len());
+    for attribute in attributes {
+        let built = match attribute.inner {
+            ast::Meta::NameValue(ref name, ref symbol)
+                if (name == "export_name" || name == "link_name") &&
+                   (symbol.is_empty() || symbol.contains('\0')) => {
+                return Err(SemanticError {
+                               kind: SemanticErrorKind::MalformedAttribute { name: name.clone() },
+                               span: attribute.span,
+                           })
+            }
+            ast::Meta::Word(ref name) if name == "inline" => ir::Attribute::Inline,
+            ast::Meta::Word(ref name) if name == "noinline" => ir::Attribute::NoInline,
+            ast::Meta::Word(ref name) if name == "cold" => ir::Attribute::Cold,
+            ast::Meta::NameValue(ref name, ref symbol) if name == "export_name" && !is_extern => {
+                ir::Attribute::ExportName(symbol.clone())
+            }
+            ast::Meta::NameValue(ref name, ref symbol) if name == "link_name" && is_extern => {
+                ir::Attribute::LinkName(symbol.clone())
+            }
+            _ => return Err(attribute_error(attribute)),
+        };
+
+        let name = attribute.inner.name().to_string();
+        let conflict = names
+            .iter()
+            .find(|other| {
+                      **other == name || (name == "inline" && **other == "noinline") ||
+                      (name == "noinline" && **other == "inline")
+                  })
+            .cloned();
+        if let Some(first) = conflict {
+            return Err(SemanticError {
+                           kind: SemanticErrorKind::ConflictingAttributes {
+                               first: first,
+                               second: name,
+                           },
+                           span: attribute.span,
+                       });
+        }
+        names.push(name);
+        result.push(built);
+    }
+    Ok(result)
+}
+
+// a known attribute of the wrong form or on the wrong declaration, see ast::cfg
+fn attribute_error(attribute: Spanned<ast::Meta>) -> SemanticError {
+    let kind = match attribute.inner {
+        ast::Meta::Word(ref name) if name != "export_name" && name != "link_name" => {
+            SemanticErrorKind::MisplacedAttribute { name: name.clone() }
+        }
+        ast::Meta::NameValue(ref name, _) if name == "export_name" || name == "link_name" => {
+            SemanticErrorKind::MisplacedAttribute { name: name.clone() }
+        }
+        ref meta => SemanticErrorKind::MalformedAttribute { name: meta.name().to_string() },
+    };
+    SemanticError {
+        kind: kind,
+        span: attribute.span,
+    }
+}
+
 fn build_predeclaration(predecl: PreDeclaration,
-                        globals_table: &GlobalTable)
-                        -> Result<ir::Declaration, SemanticError> {
+                        sources: &ConstSources,
+                        globals_table: &GlobalTable,
+                        options: &CompileOptions,
+                        source_manager: &SourceManager)
+                        -> Result<BuiltDeclaration, SemanticError> {
     match predecl {
-        PreDeclaration::ExternFunction { name, ty } => {
-            Ok(ir::Declaration::ExternFunction { name: name, ty: ty })
+        PreDeclaration::ExternFunction {
+            name,
+            ty,
+            attributes,
+        } => {
+            Ok((ir::Declaration::ExternFunction {
+                    name: name,
+                    ty: ty,
+                    attributes: attributes,
+                },
+                Vec::new(),
+                Vec::new()))
         }
         PreDeclaration::Function {
             name,
             param_names,
             ty,
             stmt,
+            attributes,
             span,
         } => {
-            let mut function_builder = FunctionBuilder::new(name, ty.clone(), globals_table);
+            // the default values are checked once, even if they are never used,
+            // the struct comparisons are not registered
+            let overload = globals_table
+                .get_overloads(&name)
+                .and_then(|overloads| overloads.iter().find(|overload| overload.ty == ty));
+            let params = overload.map(|overload| &overload.params[..]).unwrap_or(&[]);
+            let mut const_functions = HashMap::new();
+            for (param, param_ty) in params.iter().zip(&ty.params_ty) {
+                if let Some(ref default) = param.default {
+                    let mut calls = ConstFunctions {
+                        sources: sources,
+                        globals_table: globals_table,
+                        options: options,
+                        source_manager: source_manager,
+                        cache: &mut const_functions,
+                        span: default.span,
+                    };
+                    call::eval_default(globals_table, &mut calls, param_ty, default.clone())?;
+                }
+            }
+
+            let mut function_builder = FunctionBuilder::new(name,
+                                                            ty.clone(),
+                                                            sources.clone(),
+                                                            globals_table,
+                                                            options,
+                                                            source_manager);
             function_builder.symbol_table.start_local_scope();
             for (index, (name, ty)) in param_names.into_iter().zip(ty.params_ty).enumerate() {
-                if !function_builder.register_param(name.inner.clone(), ty, Some(index)) {
+                if !function_builder.register_param(name.clone(), ty, Some(index)) {
                     return Err(SemanticError {
                                    kind: SemanticErrorKind::ParameterAlreadyDefined {
                                        name: name.inner,
@@ -174,13 +670,7 @@ fn build_predeclaration(predecl: PreDeclaration,
             build_compound_statement(&mut function_builder, stmt)?;
 
             if *function_builder.ty.return_ty == ir::Type::Unit {
-                let value = function_builder.new_temp_value(ir::Type::Unit);
-                function_builder.push_statement(
-                    ir::Statement::Assign(
-                        value.clone(),
-                        ir::Expression::Literal(ir::Literal::Unit)
-                    )
-                );
+                let value = build_unit_value(&mut function_builder);
                 let useless_label = function_builder.new_label();
                 function_builder.push_terminator_label(Some(ir::Terminator::Ret(value)),
                                                        useless_label);
@@ -188,28 +678,252 @@ fn build_predeclaration(predecl: PreDeclaration,
 
             function_builder.symbol_table.end_local_scope();
 
-            function_builder.into_function(span)
+            let warnings = mem::replace(&mut function_builder.warnings, Vec::new());
+            let eq_types = mem::replace(&mut function_builder.eq_types, Vec::new());
+            Ok((function_builder.into_function(attributes, span)?, warnings, eq_types))
         }
+        PreDeclaration::StaticAssert { .. } |
+        PreDeclaration::Const { .. } => unreachable!(), // see build_translation_unit
     }
 }
 
 fn build_compound_statement(fb: &mut FunctionBuilder,
                             stmt: Spanned<ast::CompoundStatement>)
                             -> Result<(), SemanticError> {
+    let ast::CompoundStatement(stmts, expr) = stmt.inner;
+    fb.symbol_table.start_local_scope();
+    fb.push_defer_scope();
+    for s in stmts {
+        build_statement(fb, s)?;
+    }
+    if let Some(expr) = expr {
+        build_expression(fb, *expr)?;
+    }
+    let deferred = fb.pop_defer_scope();
+    build_deferred(fb, deferred)?;
+    fb.symbol_table.end_local_scope();
+    Ok(())
+}
+
+// built outside of any loop, with the symbol table of their `defer`
+fn build_deferred<'a>(fb: &mut FunctionBuilder<'a>,
+                      deferred: Vec<Deferred<'a>>)
+                      -> Result<(), SemanticError> {
+    if deferred.is_empty() {
+        return Ok(());
+    }
+
+    let loop_infos = mem::replace(&mut fb.loop_infos, Vec::new());
+    let defer_scopes = mem::replace(&mut fb.defer_scopes, Vec::new());
+    let in_defer = mem::replace(&mut fb.in_defer, true);
+    for d in deferred {
+        let symbol_table = mem::replace(&mut fb.symbol_table, d.symbol_table);
+        build_expression(fb, d.expr)?;
+        fb.symbol_table = symbol_table;
+    }
+    fb.loop_infos = loop_infos;
+    fb.defer_scopes = defer_scopes;
+    fb.in_defer = in_defer;
+    Ok(())
+}
+
+// return None if the block always exits through a return, break or continue
+fn build_compound_expression(fb: &mut FunctionBuilder,
+                             stmt: Spanned<ast::CompoundStatement>)
+                             -> Result<Option<ir::Value>, SemanticError> {
+    let ast::CompoundStatement(mut stmts, expr) = stmt.inner;
+
+    // a trailing block-like statement gives its value to the block
+    let tail_is_value = expr.is_none() &&
+                        match stmts.last().map(|s| &s.inner) {
+                            Some(&ast::Statement::If { else_branch: Some(_), .. }) |
+                            Some(&ast::Statement::Loop { .. }) |
+                            Some(&ast::Statement::Compound(_)) => true,
+                            _ => false,
+                        };
+    let tail = if tail_is_value { stmts.pop() } else { None };
+    let diverges = expr.is_none() && tail.is_none() &&
+                   match stmts.last().map(|s| &s.inner) {
+                       Some(&ast::Statement::Return { .. }) |
+                       Some(&ast::Statement::Break { .. }) |
+                       Some(&ast::Statement::Continue { .. }) => true,
+                       _ => false,
+                   };
+
     fb.symbol_table.start_local_scope();
-    for s in stmt.inner.0 {
+    fb.push_defer_scope();
+    for s in stmts {
         build_statement(fb, s)?;
     }
+
+    let value = if let Some(expr) = expr {
+        let value = build_expression(fb, *expr)?;
+        Some(build_lvalue_to_rvalue(fb, value))
+    } else if let Some(tail) = tail {
+        match tail.inner {
+            ast::Statement::If {
+                if_branch,
+                elseif_branches,
+                else_branch,
+            } => {
+                let branches = vec![if_branch].into_iter().chain(elseif_branches).collect();
+                build_if_expression(fb, branches, else_branch, tail.span)?
+            }
+            ast::Statement::Loop { label, stmt } => build_loop(fb, label, stmt)?,
+            ast::Statement::Compound(c) => build_compound_expression(fb, c)?,
+            _ => unreachable!(),
+        }
+    } else if diverges {
+        None
+    } else {
+        Some(build_unit_value(fb))
+    };
+    let deferred = fb.pop_defer_scope();
+    build_deferred(fb, deferred)?;
+    fb.symbol_table.end_local_scope();
+
+    Ok(value)
+}
+
+// the branches store their value in a result local, loaded after the join
+fn build_if_expression(fb: &mut FunctionBuilder,
+                       branches: Vec<(Spanned<ast::Expression>, Spanned<ast::CompoundStatement>)>,
+                       else_branch: Option<Spanned<ast::CompoundStatement>>,
+                       span: Span)
+                       -> Result<Option<ir::Value>, SemanticError> {
+    fn mismatch(expected: ir::Type, found: ir::Type) -> SemanticErrorKind {
+        SemanticErrorKind::MismatchingTypesBranches {
+            expected: expected,
+            found: found,
+        }
+    }
+
+    let global_end_label = fb.new_label();
+    let mut result = None;
+
+    fb.symbol_table.start_local_scope();
+    for branch in branches {
+        let cond_value = build_condition(fb, branch.0)?;
+
+        let if_label = fb.new_label();
+        let else_label = fb.new_label();
+
+        fb.push_terminator_label(Some(ir::Terminator::BrCond(cond_value, if_label, else_label)),
+                                 if_label);
+        let branch_span = branch.1.span;
+        if let Some(value) = build_compound_expression(fb, branch.1)? {
+            store_result_value(fb, &mut result, value, branch_span, mismatch)?;
+        }
+        fb.push_terminator_label(Some(ir::Terminator::Br(global_end_label)), else_label);
+    }
+
+    let diverges = if let Some(branch) = else_branch {
+        let branch_span = branch.span;
+        if let Some(value) = build_compound_expression(fb, branch)? {
+            store_result_value(fb, &mut result, value, branch_span, mismatch)?;
+            false
+        } else {
+            result.is_none()
+        }
+    } else {
+        if let Some((ref ty, _)) = result {
+            if *ty != ir::Type::Unit {
+                return Err(SemanticError {
+                               kind: SemanticErrorKind::MissingElseBranch { found: ty.clone() },
+                               span: span,
+                           });
+            }
+        }
+        false
+    };
+
+    fb.push_terminator_label(None, global_end_label);
     fb.symbol_table.end_local_scope();
+
+    if diverges {
+        Ok(None)
+    } else {
+        Ok(Some(load_result_value(fb, result)))
+    }
+}
+
+// `break` stores its value in a result local, loaded after the loop
+fn build_loop(fb: &mut FunctionBuilder,
+              label: Option<String>,
+              stmt: Spanned<ast::CompoundStatement>)
+              -> Result<Option<ir::Value>, SemanticError> {
+    let continue_label = fb.new_label();
+    fb.push_terminator_label(None, continue_label);
+    let break_label = fb.new_label();
+
+    fb.push_loop_info(label, continue_label, break_label, true);
+    build_compound_statement(fb, stmt)?;
+    let loop_info = fb.pop_loop_info();
+
+    fb.push_terminator_label(Some(ir::Terminator::Br(continue_label)), break_label);
+
+    if let Some(result) = loop_info.result {
+        Ok(Some(load_result_value(fb, Some(result))))
+    } else {
+        // no break, the loop never ends
+        Ok(None)
+    }
+}
+
+// the first stored value gives the type of the slot, the local is only
+// registered for non unit types
+fn store_result_value(fb: &mut FunctionBuilder,
+                      result: &mut Option<(ir::Type, Option<ir::LocalVarId>)>,
+                      value: ir::Value,
+                      span: Span,
+                      mismatch: fn(ir::Type, ir::Type) -> SemanticErrorKind)
+                      -> Result<(), SemanticError> {
+    if result.is_none() {
+        let local = if value.ty != ir::Type::Unit {
+            Some(fb.register_local_unnamed(value.ty.clone()))
+        } else {
+            None
+        };
+        *result = Some((value.ty.clone(), local));
+    }
+
+    let (ref ty, local) = *result.as_ref().unwrap();
+    if *ty != value.ty {
+        return Err(SemanticError {
+                       kind: mismatch(ty.clone(), value.ty),
+                       span: span,
+                   });
+    }
+
+    if let Some(local) = local {
+        build_local_store(fb, local, value);
+    }
     Ok(())
 }
 
+fn load_result_value(fb: &mut FunctionBuilder,
+                     result: Option<(ir::Type, Option<ir::LocalVarId>)>)
+                     -> ir::Value {
+    if let Some((ty, Some(local))) = result {
+        build_local_load(fb, local, ty)
+    } else {
+        build_unit_value(fb)
+    }
+}
+
 fn build_statement(fb: &mut FunctionBuilder,
                    stmt: Spanned<ast::Statement>)
                    -> Result<(), SemanticError> {
     match stmt.inner {
         ast::Statement::Compound(c) => build_compound_statement(fb, c),
-        ast::Statement::Let { name, ty, expr } => {
+        ast::Statement::Let {
+            name,
+            mutable,
+            ty,
+            expr,
+        } => {
+            let stack_array = is_array_literal(&expr.inner);
+            let owned_str = !mutable && is_concatenation(&expr.inner);
             let expr_value = build_expression(fb, expr)?;
             let expr_value = build_lvalue_to_rvalue(fb, expr_value);
 
@@ -220,7 +934,7 @@ fn build_statement(fb: &mut FunctionBuilder,
             };
 
             if ty == expr_value.ty {
-                if !fb.register_local_variable(name.clone(), ty.clone()) {
+                if !fb.register_local_variable(name.clone(), ty.clone(), mutable, stmt.span) {
                     return Err(SemanticError {
                                    kind: SemanticErrorKind::LocalVariableAlreadyDefined {
                                        name: name,
@@ -230,6 +944,14 @@ fn build_statement(fb: &mut FunctionBuilder,
                 }
 
                 let (_, lval_expr) = fb.symbol_table.get_var(&name).unwrap(); //TODO optimize
+                if let ir::Expression::LocalVarLoad(id) = lval_expr {
+                    if stack_array {
+                        fb.stack_arrays.insert(id);
+                    }
+                    if owned_str && ty == ir::Type::Str {
+                        fb.owned_strs.insert(id);
+                    }
+                }
                 let lvalue = fb.new_temp_value(ir::Type::LValue(Box::new(ty)));
                 fb.push_statement(ir::Statement::Assign(lvalue.clone(), lval_expr));
                 fb.push_statement(ir::Statement::LValueSet(lvalue, expr_value));
@@ -244,20 +966,11 @@ fn build_statement(fb: &mut FunctionBuilder,
                     })
             }
         }
-        ast::Statement::Loop { stmt } => {
-            let continue_label = fb.new_label();
-            fb.push_terminator_label(None, continue_label);
-            let break_label = fb.new_label();
-
-            let old_loop_info = fb.current_loop_info;
-            fb.current_loop_info = Some((continue_label, break_label));
-            build_compound_statement(fb, stmt)?;
-            fb.current_loop_info = old_loop_info;
-
-            fb.push_terminator_label(Some(ir::Terminator::Br(continue_label)), break_label);
+        ast::Statement::Loop { label, stmt } => {
+            build_loop(fb, label, stmt)?;
             Ok(())
         }
-        ast::Statement::While { cond, stmt } => {
+        ast::Statement::While { label, cond, stmt } => {
             let error_span = cond.span;
             let continue_label = fb.new_label();
             fb.push_terminator_label(None, continue_label);
@@ -281,44 +994,145 @@ fn build_statement(fb: &mut FunctionBuilder,
                                                                  break_label)),
                                      stmt_label);
 
-            let old_loop_info = fb.current_loop_info;
-            fb.current_loop_info = Some((continue_label, break_label));
+            fb.push_loop_info(label, continue_label, break_label, false);
             build_compound_statement(fb, stmt)?;
-            fb.current_loop_info = old_loop_info;
+            fb.pop_loop_info();
 
             fb.push_terminator_label(Some(ir::Terminator::Br(continue_label)), break_label);
             fb.symbol_table.end_local_scope();
             Ok(())
         }
         ast::Statement::For {
+            label,
             name,
             init_expr,
             cond_expr,
             step_expr,
-            stmt: mut sub_stmt,
+            stmt: sub_stmt,
         } => {
-            //TODO: maybe we need a phase b/ ast and ir
-            let mut stmts = Vec::new();
             let init_span = init_expr.span;
-            let step_span = step_expr.span;
-
-            stmts.push(Spanned::new(ast::Statement::Let {
-                                        name: name,
-                                        ty: None,
-                                        expr: init_expr,
-                                    },
-                                    init_span));
-            sub_stmt
-                .inner
-                .0
-                .push(Spanned::new(ast::Statement::Expression { expr: step_expr }, step_span));
-            stmts.push(Spanned::new(ast::Statement::While {
-                                        cond: cond_expr,
-                                        stmt: sub_stmt,
-                                    },
-                                    stmt.span));
-
-            build_compound_statement(fb, Spanned::new(ast::CompoundStatement(stmts), stmt.span))
+
+            fb.symbol_table.start_local_scope();
+            build_statement(fb,
+                            Spanned::new(ast::Statement::Let {
+                                             name: name,
+                                             mutable: true,
+                                             ty: None,
+                                             expr: init_expr,
+                                         },
+                                         init_span))?;
+
+            let cond_label = fb.new_label();
+            fb.push_terminator_label(None, cond_label);
+            let cond_value = build_condition(fb, cond_expr)?;
+
+            let stmt_label = fb.new_label();
+            let step_label = fb.new_label();
+            let break_label = fb.new_label();
+            fb.push_terminator_label(Some(ir::Terminator::BrCond(cond_value,
+                                                                 stmt_label,
+                                                                 break_label)),
+                                     stmt_label);
+
+            // continue goes through the step
+            fb.push_loop_info(label, step_label, break_label, false);
+            build_compound_statement(fb, sub_stmt)?;
+            fb.pop_loop_info();
+
+            fb.push_terminator_label(None, step_label);
+            build_expression(fb, step_expr)?;
+            fb.push_terminator_label(Some(ir::Terminator::Br(cond_label)), break_label);
+            fb.symbol_table.end_local_scope();
+            Ok(())
+        }
+        ast::Statement::ForRange {
+            label,
+            name,
+            start_expr,
+            end_expr,
+            inclusive,
+            stmt: sub_stmt,
+        } => {
+            let mut bounds = Vec::with_capacity(2);
+            for bound in vec![start_expr, end_expr] {
+                let error_span = bound.span;
+                let value = build_expression(fb, bound)?;
+                let value = build_lvalue_to_rvalue(fb, value);
+                if value.ty != ir::Type::Int {
+                    return Err(SemanticError {
+                                   kind: SemanticErrorKind::RangeNotInt { found: value.ty },
+                                   span: error_span,
+                               });
+                }
+                bounds.push(value);
+            }
+            let end_value = bounds.pop().unwrap();
+            let start_value = bounds.pop().unwrap();
+
+            let counter_id = fb.register_local_unnamed(ir::Type::Int);
+            build_local_store(fb, counter_id, start_value);
+
+            let cond_label = fb.new_label();
+            fb.push_terminator_label(None, cond_label);
+            let counter_value = build_local_load(fb, counter_id, ir::Type::Int);
+            let cond_value = fb.new_temp_value(ir::Type::Bool);
+            let cmp_op = if inclusive {
+                ir::BinOpCode::IntLessEqual
+            } else {
+                ir::BinOpCode::IntLess
+            };
+            fb.push_statement(ir::Statement::Assign(cond_value.clone(),
+                                                    ir::Expression::BinOp(cmp_op,
+                                                                          counter_value.clone(),
+                                                                          end_value.clone())));
+
+            let stmt_label = fb.new_label();
+            let step_label = fb.new_label();
+            let break_label = fb.new_label();
+            fb.push_terminator_label(Some(ir::Terminator::BrCond(cond_value,
+                                                                 stmt_label,
+                                                                 break_label)),
+                                     stmt_label);
+
+            // the loop variable is an immutable copy of the hidden counter
+            fb.symbol_table.start_local_scope();
+            fb.register_local_variable(name.inner.clone(), ir::Type::Int, false, name.span);
+            let (_, lval_expr) = fb.symbol_table.get_var(&name.inner).unwrap();
+            let lvalue = fb.new_temp_value(ir::Type::LValue(Box::new(ir::Type::Int)));
+            fb.push_statement(ir::Statement::Assign(lvalue.clone(), lval_expr));
+            fb.push_statement(ir::Statement::LValueSet(lvalue, counter_value));
+
+            fb.push_loop_info(label, step_label, break_label, false);
+            build_compound_statement(fb, sub_stmt)?;
+            fb.pop_loop_info();
+            fb.symbol_table.end_local_scope();
+
+            fb.push_terminator_label(None, step_label);
+            let counter_value = build_local_load(fb, counter_id, ir::Type::Int);
+            if inclusive {
+                // stop before the increment so that `..=` can reach the int upper limit
+                let last_value = fb.new_temp_value(ir::Type::Bool);
+                fb.push_statement(ir::Statement::Assign(
+                    last_value.clone(),
+                    ir::Expression::BinOp(ir::BinOpCode::IntEqual, counter_value.clone(), end_value)
+                ));
+                let incr_label = fb.new_label();
+                fb.push_terminator_label(Some(ir::Terminator::BrCond(last_value,
+                                                                     break_label,
+                                                                     incr_label)),
+                                         incr_label);
+            }
+            let one_value = fb.new_temp_value(ir::Type::Int);
+            fb.push_statement(ir::Statement::Assign(one_value.clone(),
+                                                    ir::Expression::Literal(ir::Literal::Int(1))));
+            let next_value = fb.new_temp_value(ir::Type::Int);
+            fb.push_statement(ir::Statement::Assign(next_value.clone(),
+                                                    ir::Expression::BinOp(ir::BinOpCode::IntAdd,
+                                                                          counter_value,
+                                                                          one_value)));
+            build_local_store(fb, counter_id, next_value);
+            fb.push_terminator_label(Some(ir::Terminator::Br(cond_label)), break_label);
+            Ok(())
         }
         ast::Statement::If {
             if_branch,
@@ -361,44 +1175,72 @@ fn build_statement(fb: &mut FunctionBuilder,
             fb.symbol_table.end_local_scope();
             Ok(())
         }
-        ast::Statement::Break => {
-            if let Some((_, id)) = fb.current_loop_info {
-                fb.push_terminator(Some(ir::Terminator::Br(id)));
-                Ok(())
-            } else {
-                Err(SemanticError {
-                        kind: SemanticErrorKind::BreakOutsideLoop,
-                        span: stmt.span,
-                    })
-            }
-        }
-        ast::Statement::Continue => {
-            if let Some((id, _)) = fb.current_loop_info {
-                fb.push_terminator(Some(ir::Terminator::Br(id)));
-                Ok(())
-            } else {
-                Err(SemanticError {
-                        kind: SemanticErrorKind::ContinueOutsideLoop,
-                        span: stmt.span,
-                    })
+        ast::Statement::Break { label, expr } => {
+            fn mismatch(expected: ir::Type, found: ir::Type) -> SemanticErrorKind {
+                SemanticErrorKind::MismatchingTypesBreak {
+                    expected: expected,
+                    found: found,
+                }
             }
-        }
-        ast::Statement::Return { expr } => {
+
+            let index =
+                find_loop_index(fb, &label, SemanticErrorKind::BreakOutsideLoop, stmt.span)?;
             let (value, error_span) = if let Some(expr) = expr {
                 let error_span = expr.span;
+                if !fb.loop_infos[index].accepts_value {
+                    return Err(SemanticError {
+                                   kind: SemanticErrorKind::BreakWithValueOutsideLoop,
+                                   span: error_span,
+                               });
+                }
                 let value = build_expression(fb, expr)?;
-                let value = build_lvalue_to_rvalue(fb, value);
-                (value, error_span)
+                (build_lvalue_to_rvalue(fb, value), error_span)
             } else {
-                let value = fb.new_temp_value(ir::Type::Unit);
-                fb.push_statement(ir::Statement::Assign(
-                    value.clone(),
-                    ir::Expression::Literal(ir::Literal::Unit)
-                ));
-                (value, stmt.span)
+                (build_unit_value(fb), stmt.span)
             };
 
-            if value.ty == *fb.ty.return_ty {
+            if fb.loop_infos[index].accepts_value {
+                let mut result = fb.loop_infos[index].result.take();
+                store_result_value(fb, &mut result, value, error_span, mismatch)?;
+                fb.loop_infos[index].result = result;
+            }
+
+            let deferred = fb.deferred_since(fb.loop_infos[index].defer_depth);
+            build_deferred(fb, deferred)?;
+
+            let id = fb.loop_infos[index].break_label;
+            fb.push_terminator(Some(ir::Terminator::Br(id)));
+            Ok(())
+        }
+        ast::Statement::Continue { label } => {
+            let index =
+                find_loop_index(fb, &label, SemanticErrorKind::ContinueOutsideLoop, stmt.span)?;
+            let deferred = fb.deferred_since(fb.loop_infos[index].defer_depth);
+            build_deferred(fb, deferred)?;
+            let id = fb.loop_infos[index].continue_label;
+            fb.push_terminator(Some(ir::Terminator::Br(id)));
+            Ok(())
+        }
+        ast::Statement::Return { expr } => {
+            if fb.in_defer {
+                return Err(SemanticError {
+                               kind: SemanticErrorKind::ReturnInDefer,
+                               span: stmt.span,
+                           });
+            }
+
+            let (value, error_span) = if let Some(expr) = expr {
+                let error_span = expr.span;
+                let value = build_expression(fb, expr)?;
+                let value = build_lvalue_to_rvalue(fb, value);
+                (value, error_span)
+            } else {
+                (build_unit_value(fb), stmt.span)
+            };
+
+            if value.ty == *fb.ty.return_ty {
+                let deferred = fb.deferred_since(0);
+                build_deferred(fb, deferred)?;
                 fb.push_terminator(Some(ir::Terminator::Ret(value)));
                 Ok(())
             } else {
@@ -411,6 +1253,17 @@ fn build_statement(fb: &mut FunctionBuilder,
                     })
             }
         }
+        ast::Statement::Defer { expr } => {
+            fb.push_deferred(expr);
+            Ok(())
+        }
+        ast::Statement::StaticAssert { cond, message } => {
+            let span = cond.span;
+            with_const_functions(fb, span, |symbol_table, calls| {
+                build_static_assert(symbol_table, calls, cond, message)
+            })
+        }
+        ast::Statement::Attributed(..) => unreachable!(), // see ast::cfg
         ast::Statement::Expression { expr } => {
             build_expression(fb, expr)?;
             Ok(())
@@ -425,6 +1278,15 @@ fn build_expression(fb: &mut FunctionBuilder,
     match expr.inner {
         ast::Expression::Assign(op, lhs, rhs) => {
             let lhs_span = lhs.span;
+            if let Some((name, declared)) = immutable_variable(fb, &lhs.inner) {
+                return Err(SemanticError {
+                               kind: SemanticErrorKind::ImmutableAssignment {
+                                   name: name,
+                                   declared: declared,
+                               },
+                               span: lhs_span,
+                           });
+            }
             let lhs_value = build_expression(fb, *lhs)?;
             let rhs_value = build_expression(fb, *rhs)?;
             let rhs_value = build_lvalue_to_rvalue(fb, rhs_value);
@@ -433,27 +1295,19 @@ fn build_expression(fb: &mut FunctionBuilder,
 
                 if let Some(binop) = op {
                     let lhs_real_value = build_lvalue_to_rvalue(fb, lhs_value.clone());
-
-                    if let Some((irop, ty)) = typecheck_defs::binop_tyck(binop,
-                                                                         &lhs_real_value.ty,
-                                                                         &rhs_value.ty) {
-                        let value = fb.new_temp_value(ty);
-                        fb.push_statement(ir::Statement::Assign(
-                            value.clone(),
-                            ir::Expression::BinOp(irop, lhs_real_value, rhs_value)
-                        ));
-                        fb.push_statement(ir::Statement::LValueSet(lhs_value, value.clone()));
-                        Ok(value)
-                    } else {
-                        Err(SemanticError {
-                                kind: SemanticErrorKind::BinaryOperationUndefined {
-                                    op: binop,
-                                    lhs_ty: lhs_real_value.ty,
-                                    rhs_ty: rhs_value.ty,
-                                },
-                                span: expr.span,
-                            })
+                    let value = build_binop(fb, binop, lhs_real_value, rhs_value, expr.span)?;
+                    // an operator function may return another type
+                    if value.ty != *sub {
+                        return Err(SemanticError {
+                                       kind: SemanticErrorKind::MismatchingTypesAssignment {
+                                           expected: *sub,
+                                           found: value.ty,
+                                       },
+                                       span: expr.span,
+                                   });
                     }
+                    fb.push_statement(ir::Statement::LValueSet(lhs_value, value.clone()));
+                    Ok(value)
                 } else if *sub == rhs_value.ty.clone() {
                     fb.push_statement(ir::Statement::LValueSet(lhs_value, rhs_value.clone()));
                     Ok(rhs_value)
@@ -477,12 +1331,22 @@ fn build_expression(fb: &mut FunctionBuilder,
             let array_span = array.span;
             let index_span = index.span;
 
-            let array_value = build_expression(fb, *array)?;
+            let array_value = build_subscripted_array(fb, *array)?;
             let array_value = build_lvalue_to_rvalue(fb, array_value);
             let index_value = build_expression(fb, *index)?;
             let index_value = build_lvalue_to_rvalue(fb, index_value);
 
-            if let ir::Type::Ptr(sub) = array_value.ty.clone() {
+            if array_value.ty == ir::Type::Str && index_value.ty == ir::Type::Int {
+                let (data_value, len_value) = build_str_parts(fb, array_value);
+                Ok(build_runtime_call(fb,
+                                      runtime::STR_AT,
+                                      vec![data_value, len_value, index_value]))
+            } else if array_value.ty == ir::Type::Str {
+                Err(SemanticError {
+                        kind: SemanticErrorKind::IndexNotInt { found: index_value.ty },
+                        span: index_span,
+                    })
+            } else if let ir::Type::Ptr(sub) = array_value.ty.clone() {
                 if ir::Type::Int == index_value.ty {
                     let new_ptr_value = fb.new_temp_value(array_value.ty.clone());
                     fb.push_statement(ir::Statement::Assign(
@@ -643,29 +1507,22 @@ fn build_expression(fb: &mut FunctionBuilder,
                 let rhs_value = build_expression(fb, *rhs)?;
                 let rhs_value = build_lvalue_to_rvalue(fb, rhs_value);
 
-                if let Some((op, ty)) = typecheck_defs::binop_tyck(code,
-                                                                   &lhs_value.ty,
-                                                                   &rhs_value.ty) {
-                    let value = fb.new_temp_value(ty);
-
-                    fb.push_statement(ir::Statement::Assign(value.clone(),
-                                                            ir::Expression::BinOp(op,
-                                                                                  lhs_value,
-                                                                                  rhs_value)));
-                    Ok(value)
-                } else {
-                    Err(SemanticError {
-                            kind: SemanticErrorKind::BinaryOperationUndefined {
-                                op: code,
-                                lhs_ty: lhs_value.ty,
-                                rhs_ty: rhs_value.ty,
-                            },
-                            span: expr.span,
-                        })
-                }
+                build_binop(fb, code, lhs_value, rhs_value, expr.span)
             }
         }
         ast::Expression::UnOp(code, sub) => {
+            // the pointer could modify the variable
+            if code == ast::UnOpCode::AddressOf {
+                if let Some((name, declared)) = immutable_variable(fb, &sub.inner) {
+                    return Err(SemanticError {
+                                   kind: SemanticErrorKind::ImmutableAddressOf {
+                                       name: name,
+                                       declared: declared,
+                                   },
+                                   span: expr.span,
+                               });
+                }
+            }
             let mut sub_value = build_expression(fb, *sub)?;
             if code != ast::UnOpCode::AddressOf {
                 sub_value = build_lvalue_to_rvalue(fb, sub_value);
@@ -687,8 +1544,31 @@ fn build_expression(fb: &mut FunctionBuilder,
             }
         }
         ast::Expression::FuncCall(func, args) => {
-            let func_value = build_expression(fb, *func)?;
-            let func_value = build_ptrdecay(fb, func_value);
+            let func = *func;
+            let func_span = func.span;
+            let mut overloads = None;
+            if let ast::Expression::Identifier(ref name) = func.inner {
+                if name == "print" && !fb.symbol_table.is_defined(name) {
+                    return build_print(fb, args, expr.span);
+                }
+                if name == "assert" && !fb.symbol_table.is_defined(name) {
+                    return build_assert(fb, args, expr.span);
+                }
+                if name == "va_start" && !fb.symbol_table.is_defined(name) {
+                    return build_va_start(fb, args, expr.span);
+                }
+                if name == "va_end" && !fb.symbol_table.is_defined(name) {
+                    return build_va_end(fb, args, expr.span);
+                }
+                // the locals shadow the functions
+                if fb.symbol_table.is_mutable(name).is_none() {
+                    overloads = fb.symbol_table
+                        .globals
+                        .get_overloads(name)
+                        .map(|overloads| (name.clone(), overloads.to_vec()));
+                }
+            }
+            let printf_format = get_printf_format(fb, &func.inner, &args);
 
             fn func_ptr(ty: ir::Type) -> Option<ir::FunctionType> {
                 if let ir::Type::Ptr(ty) = ty {
@@ -702,54 +1582,115 @@ fn build_expression(fb: &mut FunctionBuilder,
                 }
             }
 
-            if let Some(func_ty) = func_ptr(func_value.ty.clone()) {
-                let mut args_span = Vec::with_capacity(args.len());
-                let mut args_ty = Vec::with_capacity(args.len());
-                let mut args_values = Vec::with_capacity(args.len());
-                for arg in args {
-                    args_span.push(arg.span);
-                    let arg = build_expression(fb, arg)?;
-                    let arg = build_lvalue_to_rvalue(fb, arg);
+            // an overloaded function is resolved once the arguments are built
+            let (func_value, params, sources, args) = match overloads {
+                Some((ref name, ref overloads)) if overloads.len() > 1 => {
+                    let (names, args): (Vec<_>, _) = call::split_named_args(args)?
+                        .into_iter()
+                        .unzip();
+                    let args = build_call_args(fb, args)?;
+                    let (overload, sources) =
+                        call::resolve_overload(name, overloads, &names, &args.1, expr.span)?;
+                    let (ty, func_expr) = fb.symbol_table.globals.get_function(name, overload.ty);
+                    let func_value = fb.new_temp_value(ty);
+                    fb.push_statement(ir::Statement::Assign(func_value.clone(), func_expr));
+                    (func_value, overload.params, sources, args)
+                }
+                overloads => {
+                    let func_value = match func.inner {
+                        ast::Expression::FieldAccess(obj, field_name) => {
+                            let obj_value = build_expression(fb, *obj)?;
+                            if is_str(&obj_value.ty) &&
+                               (field_name.inner == "c_str" || field_name.inner == "char_at") {
+                                return build_str_method(fb,
+                                                        obj_value,
+                                                        &field_name.inner,
+                                                        args,
+                                                        expr.span);
+                            }
+                            build_field_access(fb, obj_value, field_name.inner, func_span)?
+                        }
+                        inner => build_expression(fb, Spanned::new(inner, func_span))?,
+                    };
+                    let func_value = build_ptrdecay(fb, func_value);
+                    let func_ty = match func_ptr(func_value.ty.clone()) {
+                        Some(func_ty) => func_ty,
+                        None => {
+                            return Err(SemanticError {
+                                           kind: SemanticErrorKind::NonCallableType {
+                                               found: func_value.ty,
+                                           },
+                                           span: expr.span,
+                                       })
+                        }
+                    };
+                    // the function pointers have no parameter names nor default values
+                    let params = match overloads {
+                        Some((_, overloads)) => overloads[0].params.clone(),
+                        None => func_ty.params_ty.iter().map(|_| ir::Param::unnamed()).collect(),
+                    };
 
-                    args_ty.push(arg.ty.clone());
-                    args_values.push(arg);
+                    let (names, args): (Vec<_>, _) = call::split_named_args(args)?
+                        .into_iter()
+                        .unzip();
+                    let args = build_call_args(fb, args)?;
+                    let sources = call::match_args(&func_ty, &params, &names, expr.span)?;
+                    (func_value, params, sources, args)
+                }
+            };
+            let func_ty = func_ptr(func_value.ty.clone()).unwrap();
+
+            // in the order of the parameters, then the variadic arguments
+            let mut args_span = Vec::with_capacity(sources.len());
+            let mut args_ty = Vec::with_capacity(sources.len());
+            let mut args_values = Vec::with_capacity(sources.len());
+            for (index, source) in sources.into_iter().enumerate() {
+                if let Some(arg) = source {
+                    args_span.push(args.0[arg]);
+                    args_ty.push(args.1[arg].clone());
+                    args_values.push(args.2[arg].clone());
+                    continue;
                 }
 
+                let default = params[index].default.clone().unwrap(); // see call::match_args
+                args_span.push(default.span);
+                let param_ty = &func_ty.params_ty[index];
+                let lit = with_const_functions(fb, default.span, |symbol_table, calls| {
+                    call::eval_default(symbol_table.globals, calls, param_ty, default)
+                })?
+                        .to_literal();
+                let value = fb.new_temp_value(lit.ty());
+                fb.push_statement(ir::Statement::Assign(value.clone(),
+                                                        ir::Expression::Literal(lit)));
+                args_ty.push(value.ty.clone());
+                args_values.push(value);
+            }
 
-                if (func_ty.variadic && args_ty.len() < func_ty.params_ty.len()) ||
-                   (!func_ty.variadic && args_ty.len() != func_ty.params_ty.len()) {
+            for i in 0..func_ty.params_ty.len() {
+                if args_ty[i] != func_ty.params_ty[i] {
                     return Err(SemanticError {
-                                   kind: SemanticErrorKind::MismatchingParamLen {
-                                       expected: func_ty.params_ty.len(),
-                                       found: args_ty.len(),
+                                   kind: SemanticErrorKind::MismatchingTypesArgument {
+                                       expected: func_ty.params_ty[i].clone(),
+                                       found: args_ty[i].clone(),
                                    },
-                                   span: expr.span,
+                                   span: args_span[i],
                                });
                 }
+            }
 
-                for i in 0..func_ty.params_ty.len() {
-                    if args_ty[i] != func_ty.params_ty[i] {
-                        return Err(SemanticError {
-                                       kind: SemanticErrorKind::MismatchingTypesArgument {
-                                           expected: func_ty.params_ty[i].clone(),
-                                           found: args_ty[i].clone(),
-                                       },
-                                       span: args_span[i],
-                                   });
-                    }
-                }
-
-                let value = fb.new_temp_value(*func_ty.return_ty);
-                fb.push_statement(ir::Statement::Assign(value.clone(),
-                                                        ir::Expression::FuncCall(func_value,
-                                                                                 args_values)));
-                Ok(value)
-            } else {
-                Err(SemanticError {
-                        kind: SemanticErrorKind::NonCallableType { found: func_value.ty },
-                        span: expr.span,
-                    })
+            if let Some((format, format_index)) = printf_format {
+                check_printf_format(fb,
+                                    format,
+                                    &args_ty[format_index + 1..],
+                                    &args_span[format_index + 1..],
+                                    expr.span);
             }
+
+            let value = fb.new_temp_value(*func_ty.return_ty);
+            fb.push_statement(ir::Statement::Assign(value.clone(),
+                                                    ir::Expression::FuncCall(func_value,
+                                                                             args_values)));
+            Ok(value)
         }
         ast::Expression::Cast(sub_expr, target_ty) => {
             let expr_value = build_expression(fb, *sub_expr)?;
@@ -774,80 +1715,37 @@ fn build_expression(fb: &mut FunctionBuilder,
         }
         ast::Expression::FieldAccess(struct_expr, field_name) => {
             let struct_value = build_expression(fb, *struct_expr)?;
-            let struct_value = build_ptrdecay(fb, struct_value);
-
-            build_struct_field_access(fb, struct_value, field_name.inner, expr.span)
+            build_field_access(fb, struct_value, field_name.inner, expr.span)
         }
         ast::Expression::Paren(expr) => build_expression(fb, *expr),
+        ast::Expression::NamedArg(..) => {
+            Err(SemanticError {
+                    kind: SemanticErrorKind::MisplacedNamedArgument,
+                    span: expr.span,
+                })
+        }
         ast::Expression::Identifier(id) => {
-            if let Some((ty, expr)) = fb.symbol_table.get_var(&id) {
-                let value = fb.new_temp_value(ty);
-                fb.push_statement(ir::Statement::Assign(value.clone(), expr));
-                Ok(value)
-            } else {
-                Err(SemanticError {
-                        kind: SemanticErrorKind::IdentifierUndefined { name: id },
-                        span: expr.span,
-                    })
+            // the pointer could modify the items, like `&x`
+            if let Some(declared) = immutable_stack_array(fb, &id) {
+                return Err(SemanticError {
+                               kind: SemanticErrorKind::ImmutableArrayDecay {
+                                   name: id,
+                                   declared: declared,
+                               },
+                               span: expr.span,
+                           });
             }
+            build_identifier(fb, id, expr.span)
         }
         ast::Expression::Literal(lit) => {
             let lit = build_literal(lit, expr.span)?;
-            let ty = match lit {
-                ir::Literal::Int(_) => ir::Type::Int,
-                ir::Literal::Double(_) => ir::Type::Double,
-                ir::Literal::Bool(_) => ir::Type::Bool,
-                ir::Literal::Char(_) => ir::Type::Char,
-                ir::Literal::Unit => ir::Type::Unit,
-            };
-            let value = fb.new_temp_value(ty);
+            let value = fb.new_temp_value(lit.ty());
             fb.push_statement(ir::Statement::Assign(value.clone(), ir::Expression::Literal(lit)));
             Ok(value)
         }
         ast::Expression::StringLiteral(val) => {
-            let mut string = Vec::with_capacity(val.len());
-            let mut slash = false;
-            for c in val.chars() {
-                if slash {
-                    let c = match c {
-                        '\'' | '\"' => c as u8,
-                        'a' => b'\x07',
-                        'b' => b'\x08',
-                        'f' => b'\x0c',
-                        'n' => b'\n',
-                        'r' => b'\r',
-                        't' => b'\t',
-                        'v' => b'\x0b',
-                        '0' => b'\0',
-                        _ => {
-                            return Err(SemanticError {
-                                           kind: SemanticErrorKind::InvalidEscapeChar { c: c },
-                                           span: expr.span,
-                                       })
-                        }
-                    };
-                    slash = false;
-
-                    string.push(c);
-                } else if c == '\\' {
-                    slash = true;
-                } else {
-                    string.push(c as u8);
-                }
-            }
-            string.push(b'\0');
-
-            let mut values = Vec::with_capacity(string.len());
-            for c in string {
-                let value = fb.new_temp_value(ir::Type::Char);
-                fb.push_statement(ir::Statement::Assign(
-                    value.clone(),
-                    ir::Expression::Literal(ir::Literal::Char(c))
-                ));
-                values.push(value);
-            }
-
-            Ok(build_array_with_values(fb, ir::Type::Char, values))
+            let string = escape::unescape(&val, expr.span)?;
+            Ok(build_str_literal_value(fb, string))
         }
         ast::Expression::ArrayFullLiteral(exprs) => {
             let mut values = Vec::with_capacity(exprs.len());
@@ -883,6 +1781,26 @@ fn build_expression(fb: &mut FunctionBuilder,
             Ok(build_array_with_values(fb, expr_ty, values))
         }
         ast::Expression::ArrayDefaultLiteral(expr, size) => {
+            let size_span = size.span;
+            let size = with_const_functions(fb, size_span, |symbol_table, calls| {
+                const_eval::eval_expression(symbol_table, calls, *size)
+            });
+            let size = match size? {
+                const_eval::ConstValue::Int(size) if size >= 0 => size as i64,
+                const_eval::ConstValue::Int(size) => {
+                    return Err(SemanticError {
+                                   kind: SemanticErrorKind::NegativeArrayLength { len: size },
+                                   span: size_span,
+                               })
+                }
+                other => {
+                    return Err(SemanticError {
+                                   kind: SemanticErrorKind::ArrayLengthNotInt { found: other.ty() },
+                                   span: size_span,
+                               })
+                }
+            };
+
             let expr_value = build_expression(fb, *expr)?;
             let expr_value = build_lvalue_to_rvalue(fb, expr_value);
 
@@ -921,8 +1839,8 @@ fn build_expression(fb: &mut FunctionBuilder,
 
             Ok(array_value)
         }
-        ast::Expression::StructLiteral(ast::StructLiteral { name, fields }) => {
-            if let Some(ty) = fb.symbol_table.globals.get_type(&name) {
+        ast::Expression::StructLiteral(lit) => {
+            if let Some(ty) = fb.symbol_table.globals.get_type(&lit.name) {
                 let struct_id = fb.register_local_unnamed(ty.clone());
                 let struct_lvalue = fb.new_temp_value(ir::Type::LValue(Box::new(ty.clone())));
                 fb.push_statement(ir::Statement::Assign(struct_lvalue.clone(),
@@ -939,27 +1857,260 @@ fn build_expression(fb: &mut FunctionBuilder,
                     )
                 );
 
-                for field in fields {
-                    //TODO check init and non repeat
-                    let expr_value = build_expression(fb, field.inner.1)?;
-                    let expr_value = build_lvalue_to_rvalue(fb, expr_value);
-
-                    let field_lvalue = build_struct_field_access(fb,
-                                                                 struct_value.clone(),
-                                                                 field.inner.0,
-                                                                 field.span)?;
-                    fb.push_statement(ir::Statement::LValueSet(field_lvalue, expr_value));
-                }
+                build_struct_fields(fb, struct_value, lit, expr.span)?;
 
                 let struct_value = build_lvalue_to_rvalue(fb, struct_lvalue);
                 Ok(struct_value)
             } else {
                 Err(SemanticError {
-                        kind: SemanticErrorKind::UndefinedType { name: name },
+                        kind: SemanticErrorKind::UndefinedType { name: lit.name },
                         span: expr.span,
                     })
             }
         }
+        ast::Expression::NewStruct(lit) => {
+            if let Some(ty) = fb.symbol_table.globals.get_type(&lit.name) {
+                let struct_value = build_heap_alloc(fb, ty, None);
+                build_struct_fields(fb, struct_value.clone(), lit, expr.span)?;
+                Ok(struct_value)
+            } else {
+                Err(SemanticError {
+                        kind: SemanticErrorKind::UndefinedType { name: lit.name },
+                        span: expr.span,
+                    })
+            }
+        }
+        ast::Expression::NewArray(ty, len) => {
+            let len_span = len.span;
+            let ty = build_type(ty, fb.symbol_table.globals)?;
+            let len_value = build_expression(fb, *len)?;
+            let len_value = build_lvalue_to_rvalue(fb, len_value);
+
+            if len_value.ty == ir::Type::Int {
+                Ok(build_heap_alloc(fb, ty, Some(len_value)))
+            } else {
+                Err(SemanticError {
+                        kind: SemanticErrorKind::ArrayLengthNotInt { found: len_value.ty },
+                        span: len_span,
+                    })
+            }
+        }
+        ast::Expression::Delete(sub_expr) => {
+            let sub_span = sub_expr.span;
+            let owned_str = is_owned_str(fb, &sub_expr.inner);
+            let value = build_expression(fb, *sub_expr)?;
+            let value = build_lvalue_to_rvalue(fb, value);
+
+            if let ir::Type::Ptr(_) = value.ty {
+                let byte_ptr_ty = ir::Type::Ptr(Box::new(ir::Type::Byte));
+                let byte_ptr_value = fb.new_temp_value(byte_ptr_ty.clone());
+                fb.push_statement(ir::Statement::Assign(byte_ptr_value.clone(),
+                                                        ir::Expression::PtrCast(value,
+                                                                                byte_ptr_ty)));
+                Ok(build_runtime_call(fb, runtime::FREE, vec![byte_ptr_value]))
+            } else if value.ty == ir::Type::Str && owned_str {
+                let (data_value, _) = build_str_parts(fb, value);
+                Ok(build_runtime_call(fb, runtime::FREE, vec![data_value]))
+            } else if value.ty == ir::Type::Str {
+                Err(SemanticError {
+                        kind: SemanticErrorKind::UnownedStrDelete,
+                        span: sub_span,
+                    })
+            } else {
+                Err(SemanticError {
+                        kind: SemanticErrorKind::NonDeletableType { found: value.ty },
+                        span: sub_span,
+                    })
+            }
+        }
+        ast::Expression::MacroCall(..) => unreachable!(), // see ast::cfg
+        ast::Expression::SizeOf(ty) => {
+            let ty_span = ty.span;
+            let ty = build_type(ty, fb.symbol_table.globals)?;
+            if const_eval::size_of(&ty).is_none() {
+                return Err(SemanticError {
+                               kind: SemanticErrorKind::UnsizedType { ty: ty },
+                               span: ty_span,
+                           });
+            }
+
+            let value = fb.new_temp_value(ir::Type::Int);
+            fb.push_statement(ir::Statement::Assign(value.clone(), ir::Expression::SizeOf(ty)));
+            Ok(value)
+        }
+        ast::Expression::VaArg(list, ty) => {
+            let list_value = build_va_list(fb, *list)?;
+            let ty_span = ty.span;
+            let ty = build_type(ty, fb.symbol_table.globals)?;
+            match ty {
+                ir::Type::Bool | ir::Type::Int | ir::Type::Double | ir::Type::Char |
+                ir::Type::VaList | ir::Type::Ptr(_) => {}
+                _ => {
+                    return Err(SemanticError {
+                                   kind: SemanticErrorKind::NonVariadicType { ty: ty },
+                                   span: ty_span,
+                               })
+                }
+            }
+
+            let value = fb.new_temp_value(ty.clone());
+            fb.push_statement(ir::Statement::Assign(value.clone(),
+                                                    ir::Expression::VaArg(list_value, ty)));
+            Ok(value)
+        }
+        ast::Expression::If {
+            if_branch,
+            elseif_branches,
+            else_branch,
+        } => {
+            let if_branch = (*if_branch.0, if_branch.1);
+            let branches = vec![if_branch].into_iter().chain(elseif_branches).collect();
+            let value = build_if_expression(fb, branches, else_branch, expr.span)?;
+            Ok(value.unwrap_or_else(|| build_unit_value(fb)))
+        }
+        ast::Expression::Block(stmt) => {
+            let value = build_compound_expression(fb, stmt)?;
+            Ok(value.unwrap_or_else(|| build_unit_value(fb)))
+        }
+        ast::Expression::Loop { label, stmt } => {
+            let value = build_loop(fb, label, stmt)?;
+            Ok(value.unwrap_or_else(|| build_unit_value(fb)))
+        }
+    }
+}
+
+fn find_loop_index(fb: &FunctionBuilder,
+                   label: &Option<String>,
+                   outside_kind: SemanticErrorKind,
+                   span: Span)
+                   -> Result<usize, SemanticError> {
+    if let Some(index) = fb.find_loop_index(label) {
+        Ok(index)
+    } else if let Some(ref name) = *label {
+        Err(SemanticError {
+                kind: SemanticErrorKind::UndefinedLabel { name: format!("'{}", name) },
+                span: span,
+            })
+    } else {
+        Err(SemanticError {
+                kind: outside_kind,
+                span: span,
+            })
+    }
+}
+
+fn build_identifier(fb: &mut FunctionBuilder,
+                    id: String,
+                    span: Span)
+                    -> Result<ir::Value, SemanticError> {
+    if let Some((ty, expr)) = fb.symbol_table.get_var(&id) {
+        let value = fb.new_temp_value(ty);
+        fb.push_statement(ir::Statement::Assign(value.clone(), expr));
+        Ok(value)
+    } else if let Some(overloads) = fb.symbol_table.globals.get_overloads(&id) {
+        Err(SemanticError {
+                kind: SemanticErrorKind::AmbiguousOverload {
+                    name: id.clone(),
+                    candidates: overloads
+                        .iter()
+                        .map(|overload| overload.ty.clone())
+                        .collect(),
+                },
+                span: span,
+            })
+    } else {
+        Err(SemanticError {
+                kind: SemanticErrorKind::IdentifierUndefined { name: id },
+                span: span,
+            })
+    }
+}
+
+// the items are read or assigned in place, the array isn't used as a pointer
+fn build_subscripted_array(fb: &mut FunctionBuilder,
+                           array: Spanned<ast::Expression>)
+                           -> Result<ir::Value, SemanticError> {
+    match array.inner {
+        ast::Expression::Identifier(id) => build_identifier(fb, id, array.span),
+        ast::Expression::Paren(sub) => build_subscripted_array(fb, *sub),
+        _ => build_expression(fb, array),
+    }
+}
+
+// the local variable modified through expr, by an assignment or a pointer
+fn modified_variable<'a>(fb: &FunctionBuilder, expr: &'a ast::Expression) -> Option<&'a String> {
+    match *expr {
+        ast::Expression::Identifier(ref name) => Some(name),
+        ast::Expression::Paren(ref sub) => modified_variable(fb, &sub.inner),
+        // the fields are stored in the struct value, not behind a pointer
+        ast::Expression::FieldAccess(ref sub, _) => modified_variable(fb, &sub.inner),
+        // the items of an array literal belong to the variable it is bound to
+        ast::Expression::Subscript(ref sub, _) => {
+            let name = modified_variable(fb, &sub.inner)?;
+            match fb.symbol_table.get_var(name) {
+                Some((_, ir::Expression::LocalVarLoad(id))) if fb.stack_arrays.contains(&id) => {
+                    Some(name)
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+// (name, declaration) if expr can't be modified
+fn immutable_variable(fb: &FunctionBuilder, expr: &ast::Expression) -> Option<(String, Span)> {
+    let name = modified_variable(fb, expr)?;
+    match fb.symbol_table.get_local_mutability(name) {
+        Some((false, declared)) => Some((name.clone(), declared)),
+        _ => None,
+    }
+}
+
+// the declaration of name if it is an immutable array literal
+fn immutable_stack_array(fb: &FunctionBuilder, name: &String) -> Option<Span> {
+    match fb.symbol_table.get_var(name) {
+        Some((_, ir::Expression::LocalVarLoad(id))) if fb.stack_arrays.contains(&id) => {
+            match fb.symbol_table.get_local_mutability(name) {
+                Some((false, declared)) => Some(declared),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+// `a + b` on strs, which allocates its data
+fn is_concatenation(expr: &ast::Expression) -> bool {
+    match *expr {
+        ast::Expression::BinOp(ast::BinOpCode::Add, ..) => true,
+        ast::Expression::Paren(ref sub) => is_concatenation(&sub.inner),
+        _ => false,
+    }
+}
+
+// a str owns its data when it is a concatenation, or a `let` binding of one which can't be
+// reassigned, the literals don't own theirs, and neither do the copies of an owned str,
+// the temporaries of `a + b + c` and the previous value of `s += t` are leaked
+fn is_owned_str(fb: &FunctionBuilder, expr: &ast::Expression) -> bool {
+    match *expr {
+        ast::Expression::Identifier(ref name) => {
+            match fb.symbol_table.get_var(name) {
+                Some((_, ir::Expression::LocalVarLoad(id))) => fb.owned_strs.contains(&id),
+                _ => false,
+            }
+        }
+        ast::Expression::Paren(ref sub) => is_owned_str(fb, &sub.inner),
+        _ => is_concatenation(expr),
+    }
+}
+
+fn is_array_literal(expr: &ast::Expression) -> bool {
+    match *expr {
+        ast::Expression::ArrayFullLiteral(_) |
+        ast::Expression::ArrayDefaultLiteral(..) => true,
+        ast::Expression::Paren(ref sub) => is_array_literal(&sub.inner),
+        _ => false,
     }
 }
 
@@ -1011,6 +2162,1008 @@ fn build_struct_field_access(fb: &mut FunctionBuilder,
     }
 }
 
+// binop_tyck first, then the str operators
+fn build_binop(fb: &mut FunctionBuilder,
+               code: ast::BinOpCode,
+               lhs_value: ir::Value,
+               rhs_value: ir::Value,
+               span: Span)
+               -> Result<ir::Value, SemanticError> {
+    if let Some((op, ty)) = typecheck_defs::binop_tyck(code, &lhs_value.ty, &rhs_value.ty) {
+        let value = fb.new_temp_value(ty);
+        fb.push_statement(ir::Statement::Assign(value.clone(),
+                                                ir::Expression::BinOp(op, lhs_value, rhs_value)));
+        return Ok(value);
+    }
+
+    if lhs_value.ty == ir::Type::Str && rhs_value.ty == ir::Type::Str {
+        if let Some(value) = build_str_binop(fb, code, lhs_value.clone(), rhs_value.clone()) {
+            return Ok(value);
+        }
+    }
+
+    if let Some(value) = build_operator_call(fb, code, lhs_value.clone(), rhs_value.clone()) {
+        return Ok(value);
+    }
+
+    if let Some(value) = build_struct_eq(fb, code, lhs_value.clone(), rhs_value.clone(), span) {
+        return Ok(value);
+    }
+
+    Err(SemanticError {
+            kind: SemanticErrorKind::BinaryOperationUndefined {
+                op: code,
+                lhs_ty: lhs_value.ty,
+                rhs_ty: rhs_value.ty,
+            },
+            span: span,
+        })
+}
+
+// `a + b` calls `add(a, b)` when no builtin operation applies,
+// `a != b` is `!eq(a, b)` without a `ne` function
+fn build_operator_call(fb: &mut FunctionBuilder,
+                       code: ast::BinOpCode,
+                       lhs_value: ir::Value,
+                       rhs_value: ir::Value)
+                       -> Option<ir::Value> {
+    let name = match code {
+        ast::BinOpCode::Add => "add",
+        ast::BinOpCode::Sub => "sub",
+        ast::BinOpCode::Times => "mul",
+        ast::BinOpCode::Divide => "div",
+        ast::BinOpCode::Mod => "rem",
+        ast::BinOpCode::Less => "lt",
+        ast::BinOpCode::LessEqual => "le",
+        ast::BinOpCode::Greater => "gt",
+        ast::BinOpCode::GreaterEqual => "ge",
+        ast::BinOpCode::Equal => "eq",
+        ast::BinOpCode::NotEqual => "ne",
+        ast::BinOpCode::LogicalAnd |
+        ast::BinOpCode::LogicalOr => return None,
+    };
+
+    let args_ty = [lhs_value.ty.clone(), rhs_value.ty.clone()];
+    let overload = fb.symbol_table
+        .globals
+        .get_overloads(&name.to_string())
+        .and_then(|overloads| {
+                      overloads
+                          .iter()
+                          .find(|overload| {
+                                    !overload.ty.variadic && overload.ty.params_ty[..] == args_ty
+                                })
+                          .cloned()
+                  });
+    let overload = match overload {
+        Some(overload) => overload,
+        None if code == ast::BinOpCode::NotEqual => {
+            let value = build_operator_call(fb, ast::BinOpCode::Equal, lhs_value, rhs_value)?;
+            if value.ty != ir::Type::Bool {
+                return None;
+            }
+            let not_expr = ir::Expression::UnOp(ir::UnOpCode::BoolLogicalNot, value);
+            let not_value = fb.new_temp_value(ir::Type::Bool);
+            fb.push_statement(ir::Statement::Assign(not_value.clone(), not_expr));
+            return Some(not_value);
+        }
+        None => return None,
+    };
+
+    let (func_ty, func_expr) = fb.symbol_table
+        .globals
+        .get_function(&name.to_string(), overload.ty.clone());
+    let func_value = fb.new_temp_value(func_ty);
+    fb.push_statement(ir::Statement::Assign(func_value.clone(), func_expr));
+    let value = fb.new_temp_value(*overload.ty.return_ty);
+    fb.push_statement(ir::Statement::Assign(value.clone(),
+                                            ir::Expression::FuncCall(func_value,
+                                                                     vec![lhs_value, rhs_value])));
+    Some(value)
+}
+
+// `==` and `!=` on a struct without an `eq` function compare each field
+fn build_struct_eq(fb: &mut FunctionBuilder,
+                   code: ast::BinOpCode,
+                   lhs_value: ir::Value,
+                   rhs_value: ir::Value,
+                   span: Span)
+                   -> Option<ir::Value> {
+    let negate = match code {
+        ast::BinOpCode::Equal => false,
+        ast::BinOpCode::NotEqual => true,
+        _ => return None,
+    };
+    if lhs_value.ty != rhs_value.ty || !is_comparable(fb, &lhs_value.ty) {
+        return None;
+    }
+    let st = match lhs_value.ty {
+        ir::Type::Struct(ref st) => st.clone(),
+        _ => return None,
+    };
+
+    if fb.eq_types.iter().all(|&(ref other, _)| *other != st) {
+        fb.eq_types.push((st.clone(), span));
+    }
+    let ty = struct_eq_type(st);
+    let func_value = fb.new_temp_value(ir::Type::Ptr(Box::new(ir::Type::Function(ty.clone()))));
+    fb.push_statement(ir::Statement::Assign(func_value.clone(),
+                                            ir::Expression::GlobalLoad(STRUCT_EQ.to_string(),
+                                                                       ty)));
+    let value = fb.new_temp_value(ir::Type::Bool);
+    fb.push_statement(ir::Statement::Assign(value.clone(),
+                                            ir::Expression::FuncCall(func_value,
+                                                                     vec![lhs_value, rhs_value])));
+    if !negate {
+        return Some(value);
+    }
+    let not_value = fb.new_temp_value(ir::Type::Bool);
+    fb.push_statement(ir::Statement::Assign(not_value.clone(),
+                                            ir::Expression::UnOp(ir::UnOpCode::BoolLogicalNot,
+                                                                 value)));
+    Some(not_value)
+}
+
+// the types supporting `==`, a struct is compared by its `eq` function or field by field
+fn is_comparable(fb: &FunctionBuilder, ty: &ir::Type) -> bool {
+    match *ty {
+        ir::Type::Int | ir::Type::Double | ir::Type::Bool | ir::Type::Char | ir::Type::Byte |
+        ir::Type::Str => true,
+        ir::Type::Struct(ref st) => {
+            let args_ty = [ty.clone(), ty.clone()];
+            let has_eq = fb.symbol_table
+                .globals
+                .get_overloads(&"eq".to_string())
+                .map(|overloads| {
+                         overloads
+                             .iter()
+                             .any(|overload| {
+                                      !overload.ty.variadic &&
+                                      overload.ty.params_ty[..] == args_ty &&
+                                      *overload.ty.return_ty == ir::Type::Bool
+                                  })
+                     })
+                .unwrap_or(false);
+            has_eq || st.fields_ty.iter().all(|&(_, ref ty)| is_comparable(fb, ty))
+        }
+        _ => false,
+    }
+}
+
+fn struct_eq_type(st: ir::StructType) -> ir::FunctionType {
+    ir::FunctionType {
+        return_ty: Box::new(ir::Type::Bool),
+        params_ty: vec![ir::Type::Struct(st.clone()), ir::Type::Struct(st)],
+        variadic: false,
+    }
+}
+
+// `return lhs.a == rhs.a && lhs.b == rhs.b ...;`, reported at the first comparison
+fn struct_eq_predeclaration(st: ir::StructType, span: Span) -> PreDeclaration {
+    let field = |param: &str, name: &String| {
+        let param = Spanned::new(ast::Expression::Identifier(param.to_string()), span);
+        Spanned::new(ast::Expression::FieldAccess(Box::new(param),
+                                                  Spanned::new(name.clone(), span)),
+                     span)
+    };
+    let mut expr = None;
+    for &(ref name, _) in &st.fields_ty {
+        let eq = Spanned::new(ast::Expression::BinOp(ast::BinOpCode::Equal,
+                                                     Box::new(field("lhs", name)),
+                                                     Box::new(field("rhs", name))),
+                              span);
+        expr = Some(match expr {
+                        Some(prev) => {
+                            Spanned::new(ast::Expression::BinOp(ast::BinOpCode::LogicalAnd,
+                                                                Box::new(prev),
+                                                                Box::new(eq)),
+                                         span)
+                        }
+                        None => eq,
+                    });
+    }
+    let expr = expr.unwrap_or_else(|| {
+        Spanned::new(ast::Expression::Literal(ast::Literal::Bool(true)), span)
+    });
+    let stmt = Spanned::new(ast::Statement::Return { expr: Some(expr) }, span);
+
+    PreDeclaration::Function {
+        name: STRUCT_EQ.to_string(),
+        param_names: vec![Spanned::new("lhs".to_string(), span),
+                          Spanned::new("rhs".to_string(), span)],
+        ty: struct_eq_type(st),
+        stmt: Spanned::new(ast::CompoundStatement(vec![stmt], None), span),
+        attributes: vec![ir::Attribute::Inline],
+        span: span,
+    }
+}
+
+// concatenation and comparisons, None for other operators
+fn build_str_binop(fb: &mut FunctionBuilder,
+                   code: ast::BinOpCode,
+                   lhs_value: ir::Value,
+                   rhs_value: ir::Value)
+                   -> Option<ir::Value> {
+    let compare_op = match code {
+        ast::BinOpCode::Add => None,
+        ast::BinOpCode::Equal => Some(ir::BinOpCode::IntEqual),
+        ast::BinOpCode::NotEqual => Some(ir::BinOpCode::IntNotEqual),
+        ast::BinOpCode::Less => Some(ir::BinOpCode::IntLess),
+        ast::BinOpCode::LessEqual => Some(ir::BinOpCode::IntLessEqual),
+        ast::BinOpCode::Greater => Some(ir::BinOpCode::IntGreater),
+        ast::BinOpCode::GreaterEqual => Some(ir::BinOpCode::IntGreaterEqual),
+        _ => return None,
+    };
+
+    let (lhs_data, lhs_len) = build_str_parts(fb, lhs_value);
+    let (rhs_data, rhs_len) = build_str_parts(fb, rhs_value);
+
+    if let Some(compare_op) = compare_op {
+        let args = vec![lhs_data, lhs_len, rhs_data, rhs_len];
+        let compare_value = build_runtime_call(fb, runtime::STR_COMPARE, args);
+        let zero_value = fb.new_temp_value(ir::Type::Int);
+        fb.push_statement(ir::Statement::Assign(zero_value.clone(),
+                                                ir::Expression::Literal(ir::Literal::Int(0))));
+        let value = fb.new_temp_value(ir::Type::Bool);
+        fb.push_statement(ir::Statement::Assign(value.clone(),
+                                                ir::Expression::BinOp(compare_op,
+                                                                      compare_value,
+                                                                      zero_value)));
+        Some(value)
+    } else {
+        let len_value = fb.new_temp_value(ir::Type::Int);
+        fb.push_statement(ir::Statement::Assign(len_value.clone(),
+                                                ir::Expression::BinOp(ir::BinOpCode::IntAdd,
+                                                                      lhs_len.clone(),
+                                                                      rhs_len.clone())));
+        let args = vec![lhs_data, lhs_len, rhs_data, rhs_len];
+        let data_value = build_runtime_call(fb, runtime::STR_CONCAT, args);
+        Some(build_str_value(fb, data_value, len_value))
+    }
+}
+
+fn is_str(ty: &ir::Type) -> bool {
+    match *ty {
+        ir::Type::Str => true,
+        ir::Type::LValue(ref sub) => **sub == ir::Type::Str,
+        _ => false,
+    }
+}
+
+// (data, length) of a str value or lvalue
+fn build_str_parts(fb: &mut FunctionBuilder, value: ir::Value) -> (ir::Value, ir::Value) {
+    let str_ptr = build_ptrdecay(fb, value);
+    let byte_ptr_ty = ir::Type::Ptr(Box::new(ir::Type::Byte));
+    let data_lvalue = build_field_lvalue(fb, str_ptr.clone(), 0, byte_ptr_ty);
+    let len_lvalue = build_field_lvalue(fb, str_ptr, 1, ir::Type::Int);
+    (build_lvalue_to_rvalue(fb, data_lvalue), build_lvalue_to_rvalue(fb, len_lvalue))
+}
+
+fn build_str_value(fb: &mut FunctionBuilder,
+                   data_value: ir::Value,
+                   len_value: ir::Value)
+                   -> ir::Value {
+    let str_id = fb.register_local_unnamed(ir::Type::Str);
+    let str_lvalue = fb.new_temp_value(ir::Type::LValue(Box::new(ir::Type::Str)));
+    fb.push_statement(ir::Statement::Assign(str_lvalue.clone(),
+                                            ir::Expression::LocalVarLoad(str_id)));
+    let str_ptr = build_ptrdecay(fb, str_lvalue.clone());
+
+    let data_ty = data_value.ty.clone();
+    let data_lvalue = build_field_lvalue(fb, str_ptr.clone(), 0, data_ty);
+    fb.push_statement(ir::Statement::LValueSet(data_lvalue, data_value));
+    let len_lvalue = build_field_lvalue(fb, str_ptr, 1, ir::Type::Int);
+    fb.push_statement(ir::Statement::LValueSet(len_lvalue, len_value));
+
+    build_lvalue_to_rvalue(fb, str_lvalue)
+}
+
+fn build_field_lvalue(fb: &mut FunctionBuilder,
+                      struct_ptr: ir::Value,
+                      index: usize,
+                      field_ty: ir::Type)
+                      -> ir::Value {
+    let ptr_value = fb.new_temp_value(ir::Type::Ptr(Box::new(field_ty.clone())));
+    fb.push_statement(ir::Statement::Assign(ptr_value.clone(),
+                                            ir::Expression::FieldAccess(struct_ptr, index)));
+    let lvalue = fb.new_temp_value(ir::Type::LValue(Box::new(field_ty)));
+    fb.push_statement(ir::Statement::Assign(lvalue.clone(),
+                                            ir::Expression::UnOp(ir::UnOpCode::PtrDeref,
+                                                                 ptr_value)));
+    lvalue
+}
+
+// `s.c_str()` and `s.char_at(index)`, which decodes the UTF-8 sequence
+// starting at the byte index
+fn build_str_method(fb: &mut FunctionBuilder,
+                    str_value: ir::Value,
+                    name: &str,
+                    args: Vec<Spanned<ast::Expression>>,
+                    span: Span)
+                    -> Result<ir::Value, SemanticError> {
+    let (runtime_name, expected) = if name == "c_str" {
+        (runtime::STR_C_STR, 0)
+    } else {
+        (runtime::STR_DECODE, 1)
+    };
+    if args.len() != expected {
+        return Err(SemanticError {
+                       kind: SemanticErrorKind::MismatchingParamLen {
+                           expected: expected,
+                           found: args.len(),
+                           missing: Vec::new(),
+                       },
+                       span: span,
+                   });
+    }
+
+    let mut index_values = Vec::with_capacity(args.len());
+    for arg in args {
+        let arg_span = arg.span;
+        let index_value = build_expression(fb, arg)?;
+        let index_value = build_lvalue_to_rvalue(fb, index_value);
+        if index_value.ty != ir::Type::Int {
+            return Err(SemanticError {
+                           kind: SemanticErrorKind::IndexNotInt { found: index_value.ty },
+                           span: arg_span,
+                       });
+        }
+        index_values.push(index_value);
+    }
+
+    let (data_value, len_value) = build_str_parts(fb, str_value);
+    let mut call_args = vec![data_value, len_value];
+    call_args.append(&mut index_values);
+    Ok(build_runtime_call(fb, runtime_name, call_args))
+}
+
+// `len` is the only field of str
+fn build_field_access(fb: &mut FunctionBuilder,
+                      value: ir::Value,
+                      field_name: String,
+                      span: Span)
+                      -> Result<ir::Value, SemanticError> {
+    if is_str(&value.ty) {
+        if field_name == "len" {
+            let (_, len_value) = build_str_parts(fb, value);
+            return Ok(len_value);
+        }
+        return Err(SemanticError {
+                       kind: SemanticErrorKind::NoFieldInStruct {
+                           struct_ty: ir::Type::Str,
+                           field: field_name,
+                       },
+                       span: span,
+                   });
+    }
+
+    let struct_value = build_ptrdecay(fb, value);
+    build_struct_field_access(fb, struct_value, field_name, span)
+}
+
+// index of the format parameter of the printf family
+fn printf_format_index(name: &str) -> Option<usize> {
+    match name {
+        "printf" => Some(0),
+        "sprintf" | "fprintf" | "dprintf" => Some(1),
+        "snprintf" => Some(2),
+        _ => None,
+    }
+}
+
+// the format of a printf-like call, only when it is a literal like `"%d".c_str()`
+fn get_printf_format(fb: &FunctionBuilder,
+                     func: &ast::Expression,
+                     args: &[Spanned<ast::Expression>])
+                     -> Option<(Spanned<Vec<u8>>, usize)> {
+    let name = match *func {
+        ast::Expression::Identifier(ref name) => name,
+        _ => return None,
+    };
+    let format_index = printf_format_index(name)?;
+    // globals are function pointers, locals lvalues
+    match fb.symbol_table.get_var(name) {
+        Some((ir::Type::Ptr(ty), _)) => {
+            match *ty {
+                ir::Type::Function(ref ty) if ty.variadic => (),
+                _ => return None,
+            }
+        }
+        _ => return None,
+    }
+
+    let mut format = args.get(format_index)?;
+    while let ast::Expression::Paren(ref inner) = format.inner {
+        format = inner;
+    }
+    let literal = match format.inner {
+        ast::Expression::FuncCall(ref func, ref args) if args.is_empty() => {
+            match func.inner {
+                ast::Expression::FieldAccess(ref obj, ref field_name) if field_name.inner ==
+                                                                         "c_str" => {
+                    match obj.inner {
+                        ast::Expression::StringLiteral(ref val) => val,
+                        _ => return None,
+                    }
+                }
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+
+    // an invalid literal is reported when the argument itself is built
+    escape::unescape(literal, format.span)
+        .ok()
+        .map(|bytes| (Spanned::new(bytes, format.span), format_index))
+}
+
+// args and args_span start after the format, warnings don't stop the build
+fn check_printf_format(fb: &mut FunctionBuilder,
+                       format: Spanned<Vec<u8>>,
+                       args_ty: &[ir::Type],
+                       args_span: &[Span],
+                       span: Span) {
+    use self::format::PrintfArg;
+
+    let specs = match format::parse_printf_format(&format.inner) {
+        Ok(specs) => specs,
+        Err(spec) => {
+            fb.warnings
+                .push(SemanticWarning {
+                          kind: SemanticWarningKind::FormatUnsupportedSpec { spec: spec },
+                          span: format.span,
+                      });
+            return;
+        }
+    };
+
+    for (spec, (ty, &arg_span)) in specs.iter().zip(args_ty.iter().zip(args_span.iter())) {
+        let matching = match (spec.arg, ty) {
+            (PrintfArg::Int, &ir::Type::Int) |
+            (PrintfArg::Char, &ir::Type::Char) |
+            (PrintfArg::Char, &ir::Type::Int) |
+            (PrintfArg::Double, &ir::Type::Double) |
+            (PrintfArg::Pointer, &ir::Type::Ptr(_)) => true,
+            (PrintfArg::CString, &ir::Type::Ptr(ref sub_ty)) => **sub_ty == ir::Type::Byte,
+            (PrintfArg::IntPointer, &ir::Type::Ptr(ref sub_ty)) => **sub_ty == ir::Type::Int,
+            (PrintfArg::Unsupported, _) => {
+                fb.warnings
+                    .push(SemanticWarning {
+                              kind: SemanticWarningKind::FormatUnsupportedSpec {
+                                  spec: spec.spec.clone(),
+                              },
+                              span: format.span,
+                          });
+                true
+            }
+            _ => false,
+        };
+
+        if !matching {
+            fb.warnings
+                .push(SemanticWarning {
+                          kind: SemanticWarningKind::FormatMismatchingType {
+                              spec: spec.spec.clone(),
+                              found: ty.clone(),
+                          },
+                          span: arg_span,
+                      });
+        }
+    }
+
+    if specs.len() != args_ty.len() {
+        fb.warnings
+            .push(SemanticWarning {
+                      kind: SemanticWarningKind::FormatMismatchingArgsLen {
+                          expected: specs.len(),
+                          found: args_ty.len(),
+                      },
+                      span: args_span.get(specs.len()).cloned().unwrap_or(span),
+                  });
+    }
+}
+
+fn build_const<C: const_eval::CallEvaluator>(symbol_table: &SymbolTable,
+                                             calls: &mut C,
+                                             ty: Option<Spanned<ast::ParseType>>,
+                                             expr: Spanned<ast::Expression>)
+                                             -> Result<const_eval::ConstValue, SemanticError> {
+    let expr_span = expr.span;
+    let value = const_eval::eval_expression(symbol_table, calls, expr)?;
+    if let Some(ty) = ty {
+        let ty = build_type(ty, symbol_table.globals)?;
+        if ty != value.ty() {
+            return Err(SemanticError {
+                           kind: SemanticErrorKind::MismatchingTypesAssignment {
+                               expected: ty,
+                               found: value.ty(),
+                           },
+                           span: expr_span,
+                       });
+        }
+    }
+    Ok(value)
+}
+
+// the errors of the evaluation are reported at span
+fn with_const_functions<T, F>(fb: &mut FunctionBuilder, span: Span, f: F) -> T
+    where F: FnOnce(&SymbolTable, &mut ConstFunctions) -> T
+{
+    let mut calls = ConstFunctions {
+        sources: &fb.const_sources,
+        globals_table: fb.symbol_table.globals,
+        options: fb.options,
+        source_manager: fb.source_manager,
+        cache: &mut fb.const_functions,
+        span: span,
+    };
+    f(&fb.symbol_table, &mut calls)
+}
+
+fn build_static_assert<C: const_eval::CallEvaluator>(symbol_table: &SymbolTable,
+                                                     calls: &mut C,
+                                                     cond: Spanned<ast::Expression>,
+                                                     message: Option<String>)
+                                                     -> Result<(), SemanticError> {
+    let span = cond.span;
+    match const_eval::eval_expression(symbol_table, calls, cond)? {
+        const_eval::ConstValue::Bool(true) => Ok(()),
+        const_eval::ConstValue::Bool(false) => {
+            let message = match message {
+                Some(message) => {
+                    let message = escape::unescape(&message, span)?;
+                    Some(String::from_utf8_lossy(&message).into_owned())
+                }
+                None => None,
+            };
+            Err(SemanticError {
+                    kind: SemanticErrorKind::StaticAssertFailed { message: message },
+                    span: span,
+                })
+        }
+        other => {
+            Err(SemanticError {
+                    kind: SemanticErrorKind::MismatchingTypesCondition { found: other.ty() },
+                    span: span,
+                })
+        }
+    }
+}
+
+// the message is only built when the assertion fails
+fn build_assert(fb: &mut FunctionBuilder,
+                args: Vec<Spanned<ast::Expression>>,
+                span: Span)
+                -> Result<ir::Value, SemanticError> {
+    if args.is_empty() || args.len() > 2 {
+        return Err(SemanticError {
+                       kind: SemanticErrorKind::MismatchingParamLen {
+                           expected: if args.is_empty() { 1 } else { 2 },
+                           found: args.len(),
+                           missing: Vec::new(),
+                       },
+                       span: span,
+                   });
+    }
+
+    let mut args = args.into_iter();
+    let cond = args.next().unwrap();
+    let message = args.next();
+    let code_len = fb.code_len();
+    let cond_span = cond.span;
+    let cond_value = build_expression(fb, cond)?;
+    let cond_value = build_lvalue_to_rvalue(fb, cond_value);
+    if cond_value.ty != ir::Type::Bool {
+        return Err(SemanticError {
+                       kind: SemanticErrorKind::MismatchingTypesCondition { found: cond_value.ty },
+                       span: cond_span,
+                   });
+    }
+    // the arguments are still checked in release, but no code is emitted
+    if fb.options.release {
+        build_assert_message(fb, message)?;
+        fb.discard_code_since(code_len);
+        return Ok(build_unit_value(fb));
+    }
+
+    let ok_label = fb.new_label();
+    let fail_label = fb.new_label();
+    fb.push_terminator_label(Some(ir::Terminator::BrCond(cond_value, ok_label, fail_label)),
+                             fail_label);
+
+    let message_value = build_assert_message(fb, message)?;
+
+    let file = fb.source_manager
+        .get_file_path(span.source_index)
+        .display()
+        .to_string();
+    let (line, column) = fb.source_manager.get_line_column(span.source_index, span.lo);
+    let cond_text = fb.source_manager.get_text(cond_span).to_string();
+
+    let file_value = build_str_literal_value(fb, file.into_bytes());
+    let (file_data, file_len) = build_str_parts(fb, file_value);
+    let line_value = build_int_value(fb, line as i64);
+    let column_value = build_int_value(fb, column as i64);
+    let cond_value = build_str_literal_value(fb, cond_text.into_bytes());
+    let (cond_data, cond_len) = build_str_parts(fb, cond_value);
+    let (message_data, message_len) = build_str_parts(fb, message_value);
+    build_runtime_call(fb,
+                       runtime::ASSERT_FAIL,
+                       vec![file_data,
+                            file_len,
+                            line_value,
+                            column_value,
+                            cond_data,
+                            cond_len,
+                            message_data,
+                            message_len]);
+    fb.push_terminator_label(Some(ir::Terminator::Br(ok_label)), ok_label);
+
+    Ok(build_unit_value(fb))
+}
+
+// only evaluated when the assertion fails
+fn build_assert_message(fb: &mut FunctionBuilder,
+                        message: Option<Spanned<ast::Expression>>)
+                        -> Result<ir::Value, SemanticError> {
+    if let Some(message) = message {
+        let message_span = message.span;
+        let message_value = build_expression(fb, message)?;
+        if !is_str(&message_value.ty) {
+            return Err(SemanticError {
+                           kind: SemanticErrorKind::MismatchingTypesArgument {
+                               expected: ir::Type::Str,
+                               found: message_value.ty,
+                           },
+                           span: message_span,
+                       });
+        }
+        Ok(message_value)
+    } else {
+        Ok(build_str_literal_value(fb, Vec::new()))
+    }
+}
+
+fn build_va_start(fb: &mut FunctionBuilder,
+                  args: Vec<Spanned<ast::Expression>>,
+                  span: Span)
+                  -> Result<ir::Value, SemanticError> {
+    if !args.is_empty() {
+        return Err(SemanticError {
+                       kind: SemanticErrorKind::MismatchingParamLen {
+                           expected: 0,
+                           found: args.len(),
+                           missing: Vec::new(),
+                       },
+                       span: span,
+                   });
+    }
+    if !fb.ty.variadic {
+        return Err(SemanticError {
+                       kind: SemanticErrorKind::VaStartOutsideVariadic,
+                       span: span,
+                   });
+    }
+
+    let value = fb.new_temp_value(ir::Type::VaList);
+    fb.push_statement(ir::Statement::Assign(value.clone(), ir::Expression::VaStart));
+    Ok(value)
+}
+
+fn build_va_end(fb: &mut FunctionBuilder,
+                args: Vec<Spanned<ast::Expression>>,
+                span: Span)
+                -> Result<ir::Value, SemanticError> {
+    if args.len() != 1 {
+        return Err(SemanticError {
+                       kind: SemanticErrorKind::MismatchingParamLen {
+                           expected: 1,
+                           found: args.len(),
+                           missing: Vec::new(),
+                       },
+                       span: span,
+                   });
+    }
+
+    let list_value = build_va_list(fb, args.into_iter().next().unwrap())?;
+    let value = fb.new_temp_value(ir::Type::Unit);
+    fb.push_statement(ir::Statement::Assign(value.clone(), ir::Expression::VaEnd(list_value)));
+    Ok(value)
+}
+
+fn build_va_list(fb: &mut FunctionBuilder,
+                 expr: Spanned<ast::Expression>)
+                 -> Result<ir::Value, SemanticError> {
+    let span = expr.span;
+    let value = build_expression(fb, expr)?;
+    let value = build_lvalue_to_rvalue(fb, value);
+    if value.ty == ir::Type::VaList {
+        Ok(value)
+    } else {
+        Err(SemanticError {
+                kind: SemanticErrorKind::MismatchingTypesArgument {
+                    expected: ir::Type::VaList,
+                    found: value.ty,
+                },
+                span: span,
+            })
+    }
+}
+
+// the `print` builtin, unless shadowed by a declaration
+fn build_print(fb: &mut FunctionBuilder,
+               args: Vec<Spanned<ast::Expression>>,
+               span: Span)
+               -> Result<ir::Value, SemanticError> {
+    let mut args = args.into_iter();
+    let pieces = match args.next() {
+        Some(Spanned { inner: ast::Expression::StringLiteral(val), span: format_span }) => {
+            let format = escape::unescape(&val, format_span)?;
+            format::parse_print_format(&format)
+                .map_err(|placeholder| {
+                             SemanticError {
+                                 kind: SemanticErrorKind::InvalidPlaceholder {
+                                     placeholder: placeholder,
+                                 },
+                                 span: format_span,
+                             }
+                         })?
+        }
+        other => {
+            return Err(SemanticError {
+                           kind: SemanticErrorKind::FormatNotStringLiteral,
+                           span: other.map(|format| format.span).unwrap_or(span),
+                       })
+        }
+    };
+
+    let placeholders_len = pieces
+        .iter()
+        .filter(|piece| **piece == PrintPiece::Placeholder)
+        .count();
+    if placeholders_len != args.len() {
+        return Err(SemanticError {
+                       kind: SemanticErrorKind::MismatchingFormatArgs {
+                           expected: placeholders_len,
+                           found: args.len(),
+                       },
+                       span: span,
+                   });
+    }
+
+    let mut values = Vec::with_capacity(placeholders_len);
+    for arg in args {
+        let arg_span = arg.span;
+        let value = build_expression(fb, arg)?;
+        values.push((build_lvalue_to_rvalue(fb, value), arg_span));
+    }
+
+    let mut values = values.into_iter();
+    for piece in pieces {
+        match piece {
+            PrintPiece::Text(text) => {
+                let value = build_str_literal_value(fb, text);
+                build_print_value(fb, value, span)?;
+            }
+            PrintPiece::Placeholder => {
+                let (value, arg_span) = values.next().unwrap();
+                build_print_value(fb, value, arg_span)?;
+            }
+        }
+    }
+
+    Ok(build_unit_value(fb))
+}
+
+fn build_print_value(fb: &mut FunctionBuilder,
+                     value: ir::Value,
+                     span: Span)
+                     -> Result<(), SemanticError> {
+    let (name, args) = match value.ty.clone() {
+        ir::Type::Str => {
+            let (data_value, len_value) = build_str_parts(fb, value);
+            (runtime::PRINT_STR, vec![data_value, len_value])
+        }
+        ir::Type::Int => (runtime::PRINT_INT, vec![value]),
+        ir::Type::Double => (runtime::PRINT_DOUBLE, vec![value]),
+        ir::Type::Char => {
+            let int_value = fb.new_temp_value(ir::Type::Int);
+            fb.push_statement(ir::Statement::Assign(int_value.clone(),
+                                                    ir::Expression::CastOp(ir::CastCode::CharToInt,
+                                                                           value)));
+            (runtime::PRINT_CHAR, vec![int_value])
+        }
+        ir::Type::Bool => {
+            let int_value = fb.new_temp_value(ir::Type::Int);
+            fb.push_statement(ir::Statement::Assign(int_value.clone(),
+                                                    ir::Expression::CastOp(ir::CastCode::BoolToInt,
+                                                                           value)));
+            (runtime::PRINT_BOOL, vec![int_value])
+        }
+        ir::Type::Byte => {
+            let int_value = fb.new_temp_value(ir::Type::Int);
+            fb.push_statement(ir::Statement::Assign(int_value.clone(),
+                                                    ir::Expression::CastOp(ir::CastCode::ByteToInt,
+                                                                           value)));
+            (runtime::PRINT_INT, vec![int_value])
+        }
+        ir::Type::Ptr(ref sub) if **sub == ir::Type::Byte => (runtime::PRINT_C_STR, vec![value]),
+        ty => {
+            return Err(SemanticError {
+                           kind: SemanticErrorKind::UnprintableType { ty: ty },
+                           span: span,
+                       })
+        }
+    };
+
+    build_runtime_call(fb, name, args);
+    Ok(())
+}
+
+// every field is initialized once, by the literal or else by its `..base`
+fn build_struct_fields(fb: &mut FunctionBuilder,
+                       struct_value: ir::Value,
+                       lit: ast::StructLiteral,
+                       span: Span)
+                       -> Result<(), SemanticError> {
+    let struct_ty = match struct_value.ty {
+        ir::Type::Ptr(ref ty) => (**ty).clone(),
+        _ => unreachable!(),
+    };
+    let fields_ty = match struct_ty {
+        ir::Type::Struct(ref st) => st.fields_ty.clone(),
+        _ => {
+            return Err(SemanticError {
+                           kind: SemanticErrorKind::NotAStructType { ty: struct_ty },
+                           span: span,
+                       })
+        }
+    };
+
+    let mut initialized: Vec<String> = Vec::with_capacity(lit.fields.len());
+    for field in lit.fields {
+        let (name, expr) = field.inner;
+        if initialized.contains(&name) {
+            return Err(SemanticError {
+                           kind: SemanticErrorKind::FieldAlreadyInitialized { name: name },
+                           span: field.span,
+                       });
+        }
+
+        let expr_span = expr.span;
+        let expr_value = build_expression(fb, expr)?;
+        let expr_value = build_lvalue_to_rvalue(fb, expr_value);
+
+        let field_lvalue =
+            build_struct_field_access(fb, struct_value.clone(), name.clone(), field.span)?;
+        if field_lvalue.ty != ir::Type::LValue(Box::new(expr_value.ty.clone())) {
+            let expected = match field_lvalue.ty {
+                ir::Type::LValue(ty) => *ty,
+                _ => unreachable!(),
+            };
+            return Err(SemanticError {
+                           kind: SemanticErrorKind::MismatchingTypesField {
+                               expected: expected,
+                               found: expr_value.ty,
+                           },
+                           span: expr_span,
+                       });
+        }
+        fb.push_statement(ir::Statement::LValueSet(field_lvalue, expr_value));
+        initialized.push(name);
+    }
+
+    let missing: Vec<String> = fields_ty
+        .into_iter()
+        .map(|(name, _)| name)
+        .filter(|name| !initialized.contains(name))
+        .collect();
+    let base = match lit.base {
+        Some(base) => base,
+        None if missing.is_empty() => return Ok(()),
+        None => {
+            return Err(SemanticError {
+                           kind: SemanticErrorKind::UninitializedFields {
+                               name: lit.name,
+                               fields: missing,
+                           },
+                           span: span,
+                       })
+        }
+    };
+
+    let base_span = base.span;
+    let base_value = build_expression(fb, *base)?;
+    let base_ty = match base_value.ty {
+        ir::Type::LValue(ref ty) => (**ty).clone(),
+        ref ty => ty.clone(),
+    };
+    if base_ty != struct_ty {
+        return Err(SemanticError {
+                       kind: SemanticErrorKind::MismatchingTypesStructBase {
+                           expected: struct_ty,
+                           found: base_ty,
+                       },
+                       span: base_span,
+                   });
+    }
+
+    let base_value = build_ptrdecay(fb, base_value);
+    for name in missing {
+        let value = build_struct_field_access(fb, base_value.clone(), name.clone(), base_span)?;
+        let value = build_lvalue_to_rvalue(fb, value);
+        let field_lvalue = build_struct_field_access(fb, struct_value.clone(), name, base_span)?;
+        fb.push_statement(ir::Statement::LValueSet(field_lvalue, value));
+    }
+    Ok(())
+}
+
+// zeroed memory for one value, or for len values
+fn build_heap_alloc(fb: &mut FunctionBuilder, ty: ir::Type, len: Option<ir::Value>) -> ir::Value {
+    let size_value = fb.new_temp_value(ir::Type::Int);
+    fb.push_statement(ir::Statement::Assign(size_value.clone(),
+                                            ir::Expression::SizeOf(ty.clone())));
+    // the runtime multiplies them, a wrapping `int` product would allocate too little
+    let count_value = match len {
+        Some(len) => len,
+        None => {
+            let one_value = fb.new_temp_value(ir::Type::Int);
+            fb.push_statement(ir::Statement::Assign(one_value.clone(),
+                                                    ir::Expression::Literal(ir::Literal::Int(1))));
+            one_value
+        }
+    };
+
+    let char_ptr_value = build_runtime_call(fb, runtime::ALLOC, vec![count_value, size_value]);
+    let ptr_ty = ir::Type::Ptr(Box::new(ty));
+    let ptr_value = fb.new_temp_value(ptr_ty.clone());
+    fb.push_statement(ir::Statement::Assign(ptr_value.clone(),
+                                            ir::Expression::PtrCast(char_ptr_value, ptr_ty)));
+    ptr_value
+}
+
+// (spans, types, values) of the arguments of a call
+type CallArgs = (Vec<Span>, Vec<ir::Type>, Vec<ir::Value>);
+
+fn build_call_args(fb: &mut FunctionBuilder,
+                   args: Vec<Spanned<ast::Expression>>)
+                   -> Result<CallArgs, SemanticError> {
+    let mut args_span = Vec::with_capacity(args.len());
+    let mut args_ty = Vec::with_capacity(args.len());
+    let mut args_values = Vec::with_capacity(args.len());
+    for arg in args {
+        args_span.push(arg.span);
+        let arg = build_expression(fb, arg)?;
+        let arg = build_lvalue_to_rvalue(fb, arg);
+
+        args_ty.push(arg.ty.clone());
+        args_values.push(arg);
+    }
+    Ok((args_span, args_ty, args_values))
+}
+
+// the arguments must match the runtime declaration
+fn build_runtime_call(fb: &mut FunctionBuilder, name: &str, args: Vec<ir::Value>) -> ir::Value {
+    let (func_ty, func_expr) = fb.symbol_table.globals.get_var(&name.to_string()).unwrap();
+    let return_ty = match func_ty {
+        ir::Type::Ptr(ref sub_ty) => {
+            match **sub_ty {
+                ir::Type::Function(ref ty) => *ty.return_ty.clone(),
+                _ => unreachable!(),
+            }
+        }
+        _ => unreachable!(),
+    };
+
+    let func_value = fb.new_temp_value(func_ty);
+    fb.push_statement(ir::Statement::Assign(func_value.clone(), func_expr));
+    let value = fb.new_temp_value(return_ty);
+    fb.push_statement(ir::Statement::Assign(value.clone(),
+                                            ir::Expression::FuncCall(func_value, args)));
+    value
+}
+
 fn build_array_with_values(fb: &mut FunctionBuilder,
                            expr_ty: ir::Type,
                            values: Vec<ir::Value>)
@@ -1049,59 +3202,67 @@ fn build_array_with_values(fb: &mut FunctionBuilder,
     array_value
 }
 
+fn build_str_literal_value(fb: &mut FunctionBuilder, string: Vec<u8>) -> ir::Value {
+    let value = fb.new_temp_value(ir::Type::Str);
+    fb.push_statement(ir::Statement::Assign(value.clone(),
+                                            ir::Expression::Literal(ir::Literal::Str(string))));
+    value
+}
+
 fn build_literal(lit: ast::Literal, span: Span) -> Result<ir::Literal, SemanticError> {
     match lit {
         ast::Literal::Unit => Ok(ir::Literal::Unit),
         ast::Literal::Int(val) => Ok(ir::Literal::Int(val)),
         ast::Literal::Double(val) => Ok(ir::Literal::Double(val)),
         ast::Literal::Bool(val) => Ok(ir::Literal::Bool(val)),
-        ast::Literal::Char(val) => {
-            let mut output = String::with_capacity(val.len());
-            let mut slash = false;
-            for c in val.chars() {
-                if slash {
-                    let c_value = match c {
-                        '\'' | '\"' => c,
-                        'a' => '\x07',
-                        'b' => '\x08',
-                        'f' => '\x0c',
-                        'n' => '\n',
-                        'r' => '\r',
-                        't' => '\t',
-                        'v' => '\x0b',
-                        '0' => '\0',
-                        _ => {
-                            return Err(SemanticError {
-                                           kind: SemanticErrorKind::InvalidEscapeChar { c: c },
-                                           span: span,
-                                       })
-                        }
-                    };
-                    output.push(c_value);
-                    slash = false;
-                } else if c == '\\' {
-                    slash = true;
-                } else {
-                    output.push(c);
-                }
-            }
-            if output.len() > 1 {
-                Err(SemanticError {
-                        kind: SemanticErrorKind::MultipleCharLiteral,
-                        span: span,
-                    })
-            } else if output.is_empty() {
-                Err(SemanticError {
-                        kind: SemanticErrorKind::EmptyCharLiteral,
-                        span: span,
-                    })
-            } else {
-                Ok(ir::Literal::Char(output.chars().next().unwrap() as u8))
-            }
-        }
+        ast::Literal::Char(val) => Ok(ir::Literal::Char(escape::unescape_char(&val, span)?)),
+        ast::Literal::Byte(val) => Ok(ir::Literal::Byte(escape::unescape_byte(&val, span)?)),
+    }
+}
+
+fn build_condition(fb: &mut FunctionBuilder,
+                   cond: Spanned<ast::Expression>)
+                   -> Result<ir::Value, SemanticError> {
+    let error_span = cond.span;
+    let cond_value = build_expression(fb, cond)?;
+    let cond_value = build_lvalue_to_rvalue(fb, cond_value);
+
+    if cond_value.ty != ir::Type::Bool {
+        Err(SemanticError {
+                kind: SemanticErrorKind::MismatchingTypesCondition { found: cond_value.ty },
+                span: error_span,
+            })
+    } else {
+        Ok(cond_value)
     }
 }
 
+fn build_local_load(fb: &mut FunctionBuilder, id: ir::LocalVarId, ty: ir::Type) -> ir::Value {
+    let lvalue = fb.new_temp_value(ir::Type::LValue(Box::new(ty)));
+    fb.push_statement(ir::Statement::Assign(lvalue.clone(), ir::Expression::LocalVarLoad(id)));
+    build_lvalue_to_rvalue(fb, lvalue)
+}
+
+fn build_local_store(fb: &mut FunctionBuilder, id: ir::LocalVarId, value: ir::Value) {
+    let lvalue = fb.new_temp_value(ir::Type::LValue(Box::new(value.ty.clone())));
+    fb.push_statement(ir::Statement::Assign(lvalue.clone(), ir::Expression::LocalVarLoad(id)));
+    fb.push_statement(ir::Statement::LValueSet(lvalue, value));
+}
+
+fn build_unit_value(fb: &mut FunctionBuilder) -> ir::Value {
+    let value = fb.new_temp_value(ir::Type::Unit);
+    fb.push_statement(ir::Statement::Assign(value.clone(),
+                                            ir::Expression::Literal(ir::Literal::Unit)));
+    value
+}
+
+fn build_int_value(fb: &mut FunctionBuilder, val: i64) -> ir::Value {
+    let value = fb.new_temp_value(ir::Type::Int);
+    fb.push_statement(ir::Statement::Assign(value.clone(),
+                                            ir::Expression::Literal(ir::Literal::Int(val))));
+    value
+}
+
 fn build_lvalue_to_rvalue(fb: &mut FunctionBuilder, value: ir::Value) -> ir::Value {
     if let ir::Type::LValue(sub) = value.ty.clone() {
         let new_value = fb.new_temp_value(*sub);
@@ -1124,10 +3285,11 @@ fn build_ptrdecay(fb: &mut FunctionBuilder, value: ir::Value) -> ir::Value {
         ptr_value
     } else if let Some(decay_ty) = value.ty.decay_type() {
         let ptr_ty = ir::Type::Ptr(Box::new(value.ty.clone()));
-        let local_id = fb.register_local_unnamed(value.ty);
+        let local_id = fb.register_local_unnamed(value.ty.clone());
         let decay_lvalue = fb.new_temp_value(decay_ty);
         fb.push_statement(ir::Statement::Assign(decay_lvalue.clone(),
                                                 ir::Expression::LocalVarLoad(local_id)));
+        fb.push_statement(ir::Statement::LValueSet(decay_lvalue.clone(), value));
         let ptr_value = fb.new_temp_value(ptr_ty.clone());
         fb.push_statement(ir::Statement::Assign(ptr_value.clone(),
                                                 ir::Expression::UnOp(ir::UnOpCode::AddressOf,
@@ -1,6 +1,9 @@
 use std::fmt;
+
+use itertools::Itertools;
+
 use span::Span;
-use ir::Type;
+use ir::{Type, FunctionType};
 use ast;
 
 #[derive(Debug, Clone)]
@@ -15,12 +18,20 @@ pub enum SemanticErrorKind {
     ParameterAlreadyDefined { name: String },
     LocalVariableAlreadyDefined { name: String },
     FieldAlreadyDefined { name: String },
+    FieldAlreadyInitialized { name: String },
     TypeAlreadyDefined { name: String },
+    ConstAlreadyDefined { name: String },
     MismatchingTypesAssignment { expected: Type, found: Type },
     MismatchingTypesCondition { found: Type },
     MismatchingTypesReturn { expected: Type, found: Type },
     MismatchingTypesArgument { expected: Type, found: Type },
     MismatchingTypesArrayLiteral { expected: Type, found: Type },
+    MismatchingTypesBranches { expected: Type, found: Type },
+    MismatchingTypesBreak { expected: Type, found: Type },
+    MismatchingTypesStructBase { expected: Type, found: Type },
+    MismatchingTypesField { expected: Type, found: Type },
+    MissingElseBranch { found: Type },
+    UninitializedFields { name: String, fields: Vec<String> },
     UndefinedType { name: String },
     BinaryOperationUndefined {
         op: ast::BinOpCode,
@@ -29,23 +40,94 @@ pub enum SemanticErrorKind {
     },
     UnaryOperationUndefined { op: ast::UnOpCode, expr_ty: Type },
     IndexNotInt { found: Type },
+    RangeNotInt { found: Type },
+    ArrayLengthNotInt { found: Type },
+    NegativeArrayLength { len: i32 },
     NonAssignableExpression,
+    ImmutableAssignment { name: String, declared: Span },
+    ImmutableAddressOf { name: String, declared: Span },
+    ImmutableArrayDecay { name: String, declared: Span },
     NonSubscriptableType { found: Type },
+    NonDeletableType { found: Type },
+    UnownedStrDelete,
     NonCallableType { found: Type },
-    MismatchingParamLen { expected: usize, found: usize },
+    NoMatchingOverload {
+        name: String,
+        args_ty: Vec<Type>,
+        candidates: Vec<FunctionType>,
+    },
+    AmbiguousOverload {
+        name: String,
+        candidates: Vec<FunctionType>,
+    },
+    AmbiguousDefaultValues { name: String, other: FunctionType },
+    MismatchingParamLen {
+        expected: usize,
+        found: usize,
+        missing: Vec<String>,
+    },
+    UnknownParameter { name: String },
+    ArgumentAlreadySupplied { name: String },
+    PositionalAfterNamedArgument,
+    MisplacedNamedArgument,
+    MismatchingFormatArgs { expected: usize, found: usize },
+    FormatNotStringLiteral,
+    InvalidPlaceholder { placeholder: String },
+    UnprintableType { ty: Type },
+    NonVariadicType { ty: Type },
+    UnsizedType { ty: Type },
+    NonConstantExpression,
+    ConstDivisionByZero,
+    ConstDivisionOverflow,
+    StaticAssertFailed { message: Option<String> },
+    ConstEvalStepLimit { limit: usize },
+    ConstEvalMemoryLimit { limit: usize },
+    ConstEvalRecursionLimit { limit: usize },
+    ConstEvalExternCall { name: String },
+    ConstEvalInvalidMemoryAccess,
+    ConstEvalAssertFailed { cond: String, message: Option<String> },
+    ConstFunctionCycle { name: String },
+    VaStartOutsideVariadic,
+    UnknownAttribute { name: String },
+    MalformedAttribute { name: String },
+    MisplacedAttribute { name: String },
+    ConflictingAttributes { first: String, second: String },
+    SymbolAlreadyDefined { symbol: String },
+    InvalidCfgPredicate,
+    UnknownMacro { name: String },
     CastUndefined { expr_ty: Type, target_ty: Type },
     NoFieldInStruct { struct_ty: Type, field: String },
     NotAStructType { ty: Type },
     IdentifierUndefined { name: String },
     InvalidEscapeChar { c: char },
+    InvalidHexEscape,
+    InvalidUnicodeEscape,
+    NonAsciiByteLiteral { c: char },
+    NonAsciiHexEscape,
+    InvalidCharCast { value: i32 },
     MultipleCharLiteral,
     EmptyCharLiteral,
     EmptyArrayLiteral,
     BreakOutsideLoop,
     ContinueOutsideLoop,
+    UndefinedLabel { name: String },
+    BreakWithValueOutsideLoop,
+    ReturnInDefer,
     NotAllPathsReturnAValue,
 }
 
+impl SemanticErrorKind {
+    // shown along with the span of the error
+    pub fn related_span(&self) -> Option<Span> {
+        match *self {
+            SemanticErrorKind::ImmutableAssignment { declared, .. } |
+            SemanticErrorKind::ImmutableAddressOf { declared, .. } |
+            SemanticErrorKind::ImmutableArrayDecay { declared, .. } => Some(declared),
+            _ => None,
+        }
+    }
+}
+
 impl fmt::Display for SemanticErrorKind {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         use self::SemanticErrorKind::*;
@@ -62,9 +144,13 @@ impl fmt::Display for SemanticErrorKind {
             FieldAlreadyDefined { ref name } => {
                 write!(f, "'{}' field is already defined in this struct.", name)
             }
+            FieldAlreadyInitialized { ref name } => {
+                write!(f, "'{}' field is already initialized in this literal.", name)
+            }
             TypeAlreadyDefined { ref name } => {
                 write!(f, "'{}' is already defined as a type.", name)
             }
+            ConstAlreadyDefined { ref name } => write!(f, "'{}' is already defined.", name),
             MismatchingTypesAssignment {
                 ref expected,
                 ref found,
@@ -107,6 +193,56 @@ impl fmt::Display for SemanticErrorKind {
                        expected,
                        found)
             }
+            MismatchingTypesBranches {
+                ref expected,
+                ref found,
+            } => {
+                write!(f,
+                       "Mismatching types in branches. Expected '{}', found '{}'.",
+                       expected,
+                       found)
+            }
+            MismatchingTypesBreak {
+                ref expected,
+                ref found,
+            } => {
+                write!(f,
+                       "Mismatching types in break statement. Expected '{}', found '{}'.",
+                       expected,
+                       found)
+            }
+            MismatchingTypesStructBase {
+                ref expected,
+                ref found,
+            } => {
+                write!(f,
+                       "Mismatching types in struct update. Expected '{}', found '{}'.",
+                       expected,
+                       found)
+            }
+            MismatchingTypesField {
+                ref expected,
+                ref found,
+            } => {
+                write!(f,
+                       "Mismatching types in struct field. Expected '{}', found '{}'.",
+                       expected,
+                       found)
+            }
+            MissingElseBranch { ref found } => {
+                write!(f,
+                       "This 'if' has no 'else' branch, it can't produce a '{}' value.",
+                       found)
+            }
+            UninitializedFields {
+                ref name,
+                ref fields,
+            } => {
+                write!(f,
+                       "'{}' literal doesn't initialize '{}'.",
+                       name,
+                       fields.iter().join("', '"))
+            }
             UndefinedType { ref name } => write!(f, "'{}' is not defined as a type.", name),
             BinaryOperationUndefined {
                 op,
@@ -128,17 +264,178 @@ impl fmt::Display for SemanticErrorKind {
                        Type::Int,
                        found)
             }
+            RangeNotInt { ref found } => {
+                write!(f,
+                       "Mismatching types in range bound. Expected '{}', found '{}'.",
+                       Type::Int,
+                       found)
+            }
+            ArrayLengthNotInt { ref found } => {
+                write!(f,
+                       "Mismatching types in array length. Expected '{}', found '{}'.",
+                       Type::Int,
+                       found)
+            }
+            NegativeArrayLength { len } => write!(f, "'{}' is not a valid array length.", len),
             NonAssignableExpression => write!(f, "This expression is not assignable."),
+            ImmutableAssignment { ref name, .. } => {
+                write!(f, "'{}' is immutable and can't be assigned.", name)
+            }
+            ImmutableAddressOf { ref name, .. } => {
+                write!(f,
+                       "'{}' is immutable, its address can't be taken to modify it.",
+                       name)
+            }
+            ImmutableArrayDecay { ref name, .. } => {
+                write!(f,
+                       "'{}' is immutable, it can't be used as a pointer to modify its items.",
+                       name)
+            }
             NonSubscriptableType { ref found } => {
                 write!(f, "'{}' type is not subscriptable.", found)
             }
+            NonDeletableType { ref found } => {
+                write!(f, "'{}' type is not a pointer or a str and can't be deleted.", found)
+            }
+            UnownedStrDelete => {
+                write!(f,
+                       "Only a concatenation, or an immutable binding of one, owns its data \
+                        and can be deleted.")
+            }
             NonCallableType { ref found } => write!(f, "'{}' type is not callable", found),
-            MismatchingParamLen { expected, found } => {
+            NoMatchingOverload {
+                ref name,
+                ref args_ty,
+                ref candidates,
+            } => {
+                write!(f,
+                       "No overload of '{}' takes the arguments '({})'. Candidates are: '{}'.",
+                       name,
+                       args_ty.iter().join(", "),
+                       candidates.iter().join("', '"))
+            }
+            AmbiguousOverload {
+                ref name,
+                ref candidates,
+            } => {
+                write!(f,
+                       "Ambiguous use of the overloaded function '{}'. Candidates are: '{}'.",
+                       name,
+                       candidates.iter().join("', '"))
+            }
+            AmbiguousDefaultValues {
+                ref name,
+                ref other,
+            } => {
+                write!(f,
+                       "'{}' can't be told apart from its overload '{}' by the calls.",
+                       name,
+                       other)
+            }
+            MismatchingParamLen {
+                expected,
+                found,
+                ref missing,
+            } => {
                 write!(f,
                        "This function takes '{}' parameters, but '{}' arguments were supplied.",
                        expected,
+                       found)?;
+                if !missing.is_empty() {
+                    write!(f, " Missing '{}'.", missing.iter().join("', '"))?;
+                }
+                Ok(())
+            }
+            UnknownParameter { ref name } => {
+                write!(f, "This function has no parameter named '{}'.", name)
+            }
+            ArgumentAlreadySupplied { ref name } => {
+                write!(f, "'{}' parameter is already supplied.", name)
+            }
+            PositionalAfterNamedArgument => {
+                write!(f, "Positional arguments must come before the named ones.")
+            }
+            MisplacedNamedArgument => {
+                write!(f, "Named arguments are only allowed in function calls.")
+            }
+            MismatchingFormatArgs { expected, found } => {
+                write!(f,
+                       "This format string takes '{}' arguments, but '{}' were supplied.",
+                       expected,
                        found)
             }
+            FormatNotStringLiteral => write!(f, "The format must be a string literal."),
+            InvalidPlaceholder { ref placeholder } => {
+                write!(f,
+                       "'{}' is not a valid placeholder, use '{{}}' or escape braces as '{{{{' \
+                        and '}}}}'.",
+                       placeholder)
+            }
+            UnprintableType { ref ty } => write!(f, "'{}' type can't be printed.", ty),
+            NonVariadicType { ref ty } => {
+                write!(f, "'{}' type can't be read from variadic arguments.", ty)
+            }
+            UnsizedType { ref ty } => write!(f, "'{}' type has no size.", ty),
+            NonConstantExpression => write!(f, "This expression is not constant."),
+            ConstDivisionByZero => write!(f, "Division by zero in a constant expression."),
+            ConstDivisionOverflow => {
+                write!(f, "Overflow of a division in a constant expression.")
+            }
+            StaticAssertFailed { ref message } => {
+                if let Some(ref message) = *message {
+                    write!(f, "Static assertion failed: {}", message)
+                } else {
+                    write!(f, "Static assertion failed.")
+                }
+            }
+            ConstEvalStepLimit { limit } => {
+                write!(f, "The constant evaluation exceeded the limit of {} steps.", limit)
+            }
+            ConstEvalMemoryLimit { limit } => {
+                write!(f, "The constant evaluation exceeded the limit of {} memory slots.", limit)
+            }
+            ConstEvalRecursionLimit { limit } => {
+                write!(f, "The constant evaluation exceeded the limit of {} nested calls.", limit)
+            }
+            ConstEvalExternCall { ref name } => {
+                write!(f, "'{}' can't be called in a constant evaluation.", name)
+            }
+            ConstEvalInvalidMemoryAccess => {
+                write!(f, "Invalid memory access in a constant evaluation.")
+            }
+            ConstEvalAssertFailed {
+                ref cond,
+                ref message,
+            } => {
+                write!(f, "Assertion '{}' failed in a constant evaluation", cond)?;
+                if let Some(ref message) = *message {
+                    write!(f, ": {}", message)
+                } else {
+                    write!(f, ".")
+                }
+            }
+            ConstFunctionCycle { ref name } => {
+                write!(f, "'{}' is called by a constant expression of its own body.", name)
+            }
+            VaStartOutsideVariadic => {
+                write!(f, "'va_start' is only allowed inside of a variadic function.")
+            }
+            UnknownAttribute { ref name } => write!(f, "'{}' is not a known attribute.", name),
+            MalformedAttribute { ref name } => write!(f, "Malformed '{}' attribute.", name),
+            MisplacedAttribute { ref name } => {
+                write!(f, "The '{}' attribute is not allowed here.", name)
+            }
+            ConflictingAttributes { ref first, ref second } => {
+                write!(f, "The '{}' attribute conflicts with '{}'.", second, first)
+            }
+            SymbolAlreadyDefined { ref symbol } => {
+                write!(f, "'{}' is already the symbol of another function.", symbol)
+            }
+            InvalidCfgPredicate => {
+                write!(f,
+                       "Expected 'key', 'key = \"value\"', 'not(..)', 'all(..)' or 'any(..)'.")
+            }
+            UnknownMacro { ref name } => write!(f, "'{}!' is not a known macro.", name),
             CastUndefined {
                 ref expr_ty,
                 ref target_ty,
@@ -155,12 +452,66 @@ impl fmt::Display for SemanticErrorKind {
             NotAStructType { ref ty } => write!(f, "'{}' is not a struct type.", ty),
             IdentifierUndefined { ref name } => write!(f, "'{}' is not defined here.", name),
             InvalidEscapeChar { c } => write!(f, "'{}' is not a valide escape character.", c),
+            InvalidHexEscape => write!(f, "Expected two hexadecimal digits after '\\x'."),
+            InvalidUnicodeEscape => {
+                write!(f, "Expected a valid code point in '\\u{{...}}'.")
+            }
+            NonAsciiByteLiteral { c } => {
+                write!(f, "'{}' is not an ASCII character, use a char literal.", c)
+            }
+            NonAsciiHexEscape => {
+                write!(f, "A '\\x' escape above 0x7f is not a char, use '\\u{{..}}'.")
+            }
+            InvalidCharCast { value } => write!(f, "{} is not a valid char.", value),
             MultipleCharLiteral => write!(f, "Multiple characters in a character literal."),
             EmptyCharLiteral => write!(f, "Empty character literal."),
             EmptyArrayLiteral => write!(f, "Empty array literal."),
             BreakOutsideLoop => write!(f, "'break' outside of loop."),
             ContinueOutsideLoop => write!(f, "'continue' outside of loop."),
+            UndefinedLabel { ref name } => {
+                write!(f, "{} is not the label of an enclosing loop.", name)
+            }
+            BreakWithValueOutsideLoop => {
+                write!(f, "'break' with a value is only allowed inside 'loop'.")
+            }
+            ReturnInDefer => write!(f, "'return' inside of a deferred expression."),
             NotAllPathsReturnAValue => write!(f, "Not all paths return a value."),
         }
     }
 }
+
+#[derive(Debug, Clone)]
+pub struct SemanticWarning {
+    pub kind: SemanticWarningKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum SemanticWarningKind {
+    FormatMismatchingType { spec: String, found: Type },
+    FormatMismatchingArgsLen { expected: usize, found: usize },
+    FormatUnsupportedSpec { spec: String },
+}
+
+impl fmt::Display for SemanticWarningKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::SemanticWarningKind::*;
+        match *self {
+            FormatMismatchingType { ref spec, ref found } => {
+                write!(f,
+                       "Format specification '{}' doesn't match an argument of type '{}'.",
+                       spec,
+                       found)
+            }
+            FormatMismatchingArgsLen { expected, found } => {
+                write!(f,
+                       "This format string takes '{}' arguments, but '{}' were supplied.",
+                       expected,
+                       found)
+            }
+            FormatUnsupportedSpec { ref spec } => {
+                write!(f, "'{}' is not a supported format specification.", spec)
+            }
+        }
+    }
+}
@@ -35,6 +35,20 @@ pub fn binop_tyck(op: ast::BinOpCode,
             Some((ir::BinOpCode::DoubleGreaterEqual, Type::Bool))
         }
 
+        (Less, &Type::Char, &Type::Char) => Some((ir::BinOpCode::CharLess, Type::Bool)),
+        (LessEqual, &Type::Char, &Type::Char) => Some((ir::BinOpCode::CharLessEqual, Type::Bool)),
+        (Greater, &Type::Char, &Type::Char) => Some((ir::BinOpCode::CharGreater, Type::Bool)),
+        (GreaterEqual, &Type::Char, &Type::Char) => {
+            Some((ir::BinOpCode::CharGreaterEqual, Type::Bool))
+        }
+
+        (Less, &Type::Byte, &Type::Byte) => Some((ir::BinOpCode::ByteLess, Type::Bool)),
+        (LessEqual, &Type::Byte, &Type::Byte) => Some((ir::BinOpCode::ByteLessEqual, Type::Bool)),
+        (Greater, &Type::Byte, &Type::Byte) => Some((ir::BinOpCode::ByteGreater, Type::Bool)),
+        (GreaterEqual, &Type::Byte, &Type::Byte) => {
+            Some((ir::BinOpCode::ByteGreaterEqual, Type::Bool))
+        }
+
         (Equal, &Type::Int, &Type::Int) => Some((ir::BinOpCode::IntEqual, Type::Bool)),
         (NotEqual, &Type::Int, &Type::Int) => Some((ir::BinOpCode::IntNotEqual, Type::Bool)),
         (Equal, &Type::Double, &Type::Double) => Some((ir::BinOpCode::DoubleEqual, Type::Bool)),
@@ -43,6 +57,10 @@ pub fn binop_tyck(op: ast::BinOpCode,
         }
         (Equal, &Type::Bool, &Type::Bool) => Some((ir::BinOpCode::BoolEqual, Type::Bool)),
         (NotEqual, &Type::Bool, &Type::Bool) => Some((ir::BinOpCode::BoolNotEqual, Type::Bool)),
+        (Equal, &Type::Char, &Type::Char) => Some((ir::BinOpCode::CharEqual, Type::Bool)),
+        (NotEqual, &Type::Char, &Type::Char) => Some((ir::BinOpCode::CharNotEqual, Type::Bool)),
+        (Equal, &Type::Byte, &Type::Byte) => Some((ir::BinOpCode::ByteEqual, Type::Bool)),
+        (NotEqual, &Type::Byte, &Type::Byte) => Some((ir::BinOpCode::ByteNotEqual, Type::Bool)),
 
         //(Add, &Type::Ptr(_), &Type::Int) => Some((ir::BinOpCode::PtrAdd, lhs_ty.clone())),
         _ => None,
@@ -71,6 +89,10 @@ pub fn cast_tyck(expr_ty: &ir::Type, target_ty: &ir::Type) -> Option<ir::CastCod
         (&Type::Double, &Type::Int) => Some(ir::CastCode::DoubleToInt),
         (&Type::Int, &Type::Char) => Some(ir::CastCode::IntToChar),
         (&Type::Char, &Type::Int) => Some(ir::CastCode::CharToInt),
+        (&Type::Int, &Type::Byte) => Some(ir::CastCode::IntToByte),
+        (&Type::Byte, &Type::Int) => Some(ir::CastCode::ByteToInt),
+        (&Type::Byte, &Type::Char) => Some(ir::CastCode::ByteToChar),
+        (&Type::Char, &Type::Byte) => Some(ir::CastCode::CharToByte),
         (&Type::Int, &Type::Bool) => Some(ir::CastCode::IntToBool),
         (&Type::Bool, &Type::Int) => Some(ir::CastCode::BoolToInt),
         _ => None,
@@ -1,10 +1,13 @@
+use std::fmt;
+
 pub mod builder;
 pub mod printer;
 pub mod opt;
+pub mod runtime;
 pub mod symbol_table;
 pub mod ty;
 
-pub use self::symbol_table::{SymbolTable, GlobalTable};
+pub use self::symbol_table::{SymbolTable, GlobalTable, Overload, Param};
 pub use self::ty::{Type, FunctionType, StructType};
 
 #[derive(Debug, Clone)]
@@ -14,15 +17,55 @@ pub struct TranslationUnit {
 
 #[derive(Debug, Clone)]
 pub enum Declaration {
-    ExternFunction { name: String, ty: FunctionType },
+    ExternFunction {
+        name: String,
+        ty: FunctionType,
+        attributes: Vec<Attribute>,
+    },
     Function {
         name: String,
         ty: FunctionType,
         locals: Vec<LocalVar>,
         bbs: Vec<BasicBlock>,
+        attributes: Vec<Attribute>,
     },
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Attribute {
+    Inline,
+    NoInline,
+    Cold,
+    ExportName(String), // symbol of a function
+    LinkName(String), // symbol of an extern function
+}
+
+impl Attribute {
+    // the name of the function in the generated code
+    pub fn symbol<'a>(name: &'a str, attributes: &'a [Attribute]) -> &'a str {
+        for attribute in attributes {
+            match *attribute {
+                Attribute::ExportName(ref symbol) |
+                Attribute::LinkName(ref symbol) => return symbol,
+                _ => {}
+            }
+        }
+        name
+    }
+}
+
+impl fmt::Display for Attribute {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Attribute::Inline => write!(f, "inline"),
+            Attribute::NoInline => write!(f, "noinline"),
+            Attribute::Cold => write!(f, "cold"),
+            Attribute::ExportName(ref symbol) => write!(f, "export_name = \"{}\"", symbol),
+            Attribute::LinkName(ref symbol) => write!(f, "link_name = \"{}\"", symbol),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct LocalVar {
     pub id: LocalVarId,
@@ -54,7 +97,7 @@ pub enum Statement {
 #[derive(Debug, Clone)]
 pub enum Expression {
     LocalVarLoad(LocalVarId),
-    GlobalLoad(String),
+    GlobalLoad(String, FunctionType), // an overload of the function
     LValueLoad(Value),
     BinOp(BinOpCode, Value, Value),
     UnOp(UnOpCode, Value),
@@ -62,6 +105,11 @@ pub enum Expression {
     FuncCall(Value, Vec<Value>),
     FieldAccess(Value, usize),
     Literal(Literal),
+    SizeOf(Type), // in bytes, as an int
+    PtrCast(Value, Type),
+    VaStart,
+    VaArg(Value, Type),
+    VaEnd(Value),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -69,10 +117,26 @@ pub enum Literal {
     Int(i64),
     Double(f64),
     Bool(bool),
-    Char(u8),
+    Char(char),
+    Byte(u8),
+    Str(Vec<u8>), // constant data, without the NUL terminator
     Unit,
 }
 
+impl Literal {
+    pub fn ty(&self) -> Type {
+        match *self {
+            Literal::Int(_) => Type::Int,
+            Literal::Double(_) => Type::Double,
+            Literal::Bool(_) => Type::Bool,
+            Literal::Char(_) => Type::Char,
+            Literal::Byte(_) => Type::Byte,
+            Literal::Str(_) => Type::Str,
+            Literal::Unit => Type::Unit,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 pub struct BasicBlockId(pub usize);
 
@@ -107,6 +171,14 @@ pub enum BinOpCode {
     DoubleLessEqual,
     DoubleGreater,
     DoubleGreaterEqual,
+    CharLess, // unsigned, like the byte comparisons
+    CharLessEqual,
+    CharGreater,
+    CharGreaterEqual,
+    ByteLess,
+    ByteLessEqual,
+    ByteGreater,
+    ByteGreaterEqual,
 
     IntEqual,
     IntNotEqual,
@@ -114,6 +186,10 @@ pub enum BinOpCode {
     DoubleNotEqual,
     BoolEqual,
     BoolNotEqual,
+    CharEqual,
+    CharNotEqual,
+    ByteEqual,
+    ByteNotEqual,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -129,8 +205,12 @@ pub enum UnOpCode {
 pub enum CastCode {
     IntToDouble,
     DoubleToInt,
-    IntToChar,
+    IntToChar, // aborts if the int is not a code point
     CharToInt,
+    IntToByte,
+    ByteToInt,
+    ByteToChar,
+    CharToByte,
     IntToBool,
     BoolToInt,
 }
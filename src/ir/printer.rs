@@ -1,3 +1,5 @@
+use itertools::Itertools;
+
 use ir::*;
 
 pub fn print_ir(tu: &TranslationUnit) {
@@ -8,16 +10,21 @@ pub fn print_ir(tu: &TranslationUnit) {
 
 fn print_declaration(decl: &Declaration) {
     match *decl {
-        Declaration::ExternFunction { ref name, ref ty } => {
-            println!("extern @{} {}\n", name, ty);
+        Declaration::ExternFunction {
+            ref name,
+            ref ty,
+            ref attributes,
+        } => {
+            println!("extern @{} {}{}\n", name, ty, attributes_to_string(attributes));
         }
         Declaration::Function {
             ref name,
             ref ty,
             ref locals,
             ref bbs,
+            ref attributes,
         } => {
-            println!("declare @{} {} {{", name, ty);
+            println!("declare @{} {}{} {{", name, ty, attributes_to_string(attributes));
 
             for local in locals {
                 print_local_var(local);
@@ -31,6 +38,14 @@ fn print_declaration(decl: &Declaration) {
     }
 }
 
+fn attributes_to_string(attributes: &[Attribute]) -> String {
+    if attributes.is_empty() {
+        String::new()
+    } else {
+        format!(" #[{}]", attributes.iter().join(", "))
+    }
+}
+
 fn print_local_var(var: &LocalVar) {
     print!("\tlet ${}: {} (size: {})", var.id.0, var.ty, var.size);
     if let Some(index) = var.param_index {
@@ -71,7 +86,9 @@ fn print_stmt(stmt: &Statement) {
 fn print_expr(expr: &Expression) {
     match *expr {
         Expression::LocalVarLoad(id) => print!("local_var_load(${})", id.0),
-        Expression::GlobalLoad(ref name) => print!("global_var_load(\"{}\")", name),
+        Expression::GlobalLoad(ref name, ref ty) => {
+            print!("global_var_load(\"{}\", {})", name, ty)
+        }
         Expression::LValueLoad(ref val) => {
             print!("lvalue_load(");
             print_value(val);
@@ -114,10 +131,28 @@ fn print_expr(expr: &Expression) {
                 Literal::Int(val) => print!("IntLit '{}'", val),
                 Literal::Double(val) => print!("DoubleLit '{}'", val),
                 Literal::Bool(val) => print!("BoolLit '{:?}'", val),
-                Literal::Char(val) => print!("CharLit '{}' '{}'", val, val as char),
+                Literal::Char(val) => print!("CharLit '{}' {:?}", val as u32, val),
+                Literal::Byte(val) => print!("ByteLit '{}'", val),
+                Literal::Str(ref val) => print!("StrLit {:?}", String::from_utf8_lossy(val)),
                 Literal::Unit => print!("UnitLit"),
             }
         }
+        Expression::SizeOf(ref ty) => print!("sizeof({})", ty),
+        Expression::PtrCast(ref val, ref ty) => {
+            print!("ptrcast(");
+            print_value(val);
+            print!(", {})", ty);
+        }
+        Expression::VaStart => print!("va_start"),
+        Expression::VaArg(ref val, ref ty) => {
+            print!("va_arg(");
+            print_value(val);
+            print!(", {})", ty);
+        }
+        Expression::VaEnd(ref val) => {
+            print!("va_end ");
+            print_value(val);
+        }
     }
 }
 
@@ -0,0 +1,88 @@
+use ir;
+use ir::{GlobalTable, Param};
+
+// implemented in runtime/runtime.c, linked with every executable
+// (count, size of one item), checks that the product doesn't overflow
+pub const ALLOC: &'static str = "__elang_alloc";
+pub const FREE: &'static str = "__elang_free";
+// str helpers take each str as (data, length)
+pub const STR_AT: &'static str = "__elang_str_at";
+pub const STR_COMPARE: &'static str = "__elang_str_compare";
+// the data of a concatenation is allocated, `delete` frees it, see builder::is_owned_str
+pub const STR_CONCAT: &'static str = "__elang_str_concat";
+pub const STR_C_STR: &'static str = "__elang_str_c_str";
+// (data, length, index) -> the char whose UTF-8 encoding starts at the byte index
+pub const STR_DECODE: &'static str = "__elang_str_decode";
+// used by the print builtin, bool and char are passed as int, char is printed in UTF-8
+pub const PRINT_STR: &'static str = "__elang_print_str";
+pub const PRINT_C_STR: &'static str = "__elang_print_c_str";
+pub const PRINT_INT: &'static str = "__elang_print_int";
+pub const PRINT_DOUBLE: &'static str = "__elang_print_double";
+pub const PRINT_CHAR: &'static str = "__elang_print_char";
+pub const PRINT_BOOL: &'static str = "__elang_print_bool";
+// the checked `int as char`, aborts on the surrogates and the values above U+10FFFF
+pub const CHAR_FROM_INT: &'static str = "__elang_char_from_int";
+// (file, line, column, condition, message), aborts
+pub const ASSERT_FAIL: &'static str = "__elang_assert_fail";
+
+// the builtin whose call is implemented by the helper, for the diagnostics
+pub fn builtin_name(name: &str) -> &str {
+    match name {
+        PRINT_STR | PRINT_C_STR | PRINT_INT | PRINT_DOUBLE | PRINT_CHAR | PRINT_BOOL => "print",
+        ASSERT_FAIL => "assert",
+        STR_C_STR => "c_str",
+        STR_DECODE => "char_at",
+        _ => name,
+    }
+}
+
+pub fn declarations() -> Vec<ir::Declaration> {
+    use ir::Type::{Byte, Char, Double, Int, Unit};
+    let byte_ptr = ir::Type::Ptr(Box::new(Byte));
+    let two_strs = vec![byte_ptr.clone(), Int, byte_ptr.clone(), Int];
+    vec![extern_function(ALLOC, vec![Int, Int], byte_ptr.clone()),
+         extern_function(FREE, vec![byte_ptr.clone()], Unit),
+         extern_function(STR_AT, vec![byte_ptr.clone(), Int, Int], Byte),
+         extern_function(STR_DECODE, vec![byte_ptr.clone(), Int, Int], Char),
+         extern_function(STR_COMPARE, two_strs.clone(), Int),
+         extern_function(STR_CONCAT, two_strs, byte_ptr.clone()),
+         extern_function(STR_C_STR, vec![byte_ptr.clone(), Int], byte_ptr.clone()),
+         extern_function(PRINT_STR, vec![byte_ptr.clone(), Int], Unit),
+         extern_function(PRINT_C_STR, vec![byte_ptr.clone()], Unit),
+         extern_function(PRINT_INT, vec![Int], Unit),
+         extern_function(PRINT_DOUBLE, vec![Double], Unit),
+         extern_function(PRINT_CHAR, vec![Int], Unit),
+         extern_function(PRINT_BOOL, vec![Int], Unit),
+         extern_function(CHAR_FROM_INT, vec![Int], Char),
+         extern_function(ASSERT_FAIL,
+                         vec![byte_ptr.clone(),
+                              Int,
+                              Int,
+                              Int,
+                              byte_ptr.clone(),
+                              Int,
+                              byte_ptr.clone(),
+                              Int],
+                         Unit)]
+}
+
+pub fn register_runtime(globals_table: &mut GlobalTable) {
+    for decl in declarations() {
+        if let ir::Declaration::ExternFunction { name, ty, .. } = decl {
+            let params = ty.params_ty.iter().map(|_| Param::unnamed()).collect();
+            globals_table.register_global(name, ty, params);
+        }
+    }
+}
+
+fn extern_function(name: &str, params_ty: Vec<ir::Type>, return_ty: ir::Type) -> ir::Declaration {
+    ir::Declaration::ExternFunction {
+        name: name.to_string(),
+        ty: ir::FunctionType {
+            return_ty: Box::new(return_ty),
+            params_ty: params_ty,
+            variadic: false,
+        },
+        attributes: Vec::new(),
+    }
+}
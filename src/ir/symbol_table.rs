@@ -1,11 +1,67 @@
 use std::collections::HashMap;
 
+use ast;
 use ir;
+use span::{Spanned, Span};
+
+// a parameter, as seen by the calls
+#[derive(Debug, Clone)]
+pub struct Param {
+    pub name: Option<String>, // None in the extern functions
+    pub default: Option<Spanned<ast::Expression>>, // a constant expression
+}
+
+impl Param {
+    pub fn unnamed() -> Self {
+        Param {
+            name: None,
+            default: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Overload {
+    pub ty: ir::FunctionType,
+    pub params: Vec<Param>,
+}
+
+impl Overload {
+    // the fewest positional arguments accepted, the trailing default values can be left out
+    fn min_args(&self) -> usize {
+        let len = self.ty.params_ty.len();
+        (0..len)
+            .rev()
+            .find(|&index| match self.params.get(index) {
+                      Some(param) => param.default.is_none(),
+                      None => true,
+                  })
+            .map(|index| index + 1)
+            .unwrap_or(0)
+    }
+
+    // whether a call with positional arguments only can match both,
+    // a non-variadic overload is preferred to a variadic one
+    fn conflicts_with(&self, other: &Overload) -> bool {
+        if self.ty.variadic != other.ty.variadic {
+            return false;
+        }
+        // a longer call has more types to tell them apart
+        let args = self.min_args().max(other.min_args());
+        let (len, other_len) = (self.ty.params_ty.len(), other.ty.params_ty.len());
+        if !self.ty.variadic && args > len.min(other_len) {
+            return false;
+        }
+        let typed = args.min(len).min(other_len);
+        self.ty.params_ty[..typed] == other.ty.params_ty[..typed]
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct GlobalTable {
     types: HashMap<String, ir::Type>,
-    globals: HashMap<String, ir::Type>,
+    globals: HashMap<String, Vec<Overload>>, // the overloads of each function
+    consts: HashMap<String, ir::Literal>, // inlined where used
 }
 
 impl GlobalTable {
@@ -13,24 +69,74 @@ impl GlobalTable {
         let mut g = GlobalTable {
             types: HashMap::new(),
             globals: HashMap::new(),
+            consts: HashMap::new(),
         };
 
         g.register_ty("int".to_string(), ir::Type::Int);
         g.register_ty("bool".to_string(), ir::Type::Bool);
         g.register_ty("double".to_string(), ir::Type::Double);
         g.register_ty("char".to_string(), ir::Type::Char);
+        g.register_ty("u8".to_string(), ir::Type::Byte);
+        g.register_ty("str".to_string(), ir::Type::Str);
+        g.register_ty("va_list".to_string(), ir::Type::VaList);
 
         g
     }
 
-    pub fn register_global(&mut self, name: String, ty: ir::Type) -> bool {
-        self.globals.insert(name, ty).is_none()
+    // the overloads must differ by their parameters
+    pub fn register_global(&mut self,
+                           name: String,
+                           ty: ir::FunctionType,
+                           params: Vec<Param>)
+                           -> bool {
+        if self.consts.contains_key(&name) {
+            return false;
+        }
+        let overload = Overload {
+            ty: ty,
+            params: params,
+        };
+        if self.conflicting_overload(&name, &overload).is_some() {
+            return false;
+        }
+        self.globals.entry(name).or_insert_with(Vec::new).push(overload);
+        true
+    }
+
+    // the registered overload that the calls can't tell apart from overload
+    pub fn conflicting_overload(&self, name: &String, overload: &Overload) -> Option<&Overload> {
+        self.globals
+            .get(name)
+            .and_then(|overloads| overloads.iter().find(|other| other.conflicts_with(overload)))
+    }
+
+    pub fn register_const(&mut self, name: String, lit: ir::Literal) -> bool {
+        !self.globals.contains_key(&name) && self.consts.insert(name, lit).is_none()
+    }
+
+    pub fn get_const(&self, name: &String) -> Option<ir::Literal> {
+        self.consts.get(name).cloned()
+    }
+
+    pub fn get_overloads(&self, name: &String) -> Option<&[Overload]> {
+        self.globals.get(name).map(|overloads| &overloads[..])
+    }
+
+    pub fn get_function(&self, name: &String, ty: ir::FunctionType) -> (ir::Type, ir::Expression) {
+        let ptr_ty = ir::Type::Ptr(Box::new(ir::Type::Function(ty.clone())));
+        (ptr_ty, ir::Expression::GlobalLoad(name.clone(), ty))
     }
 
+    // an overloaded function is only a value once resolved from the arguments
     pub fn get_var(&self, name: &String) -> Option<(ir::Type, ir::Expression)> {
-        if let Some(ty) = self.globals.get(name) {
-            let ptr_ty = ir::Type::Ptr(Box::new(ty.clone()));
-            Some((ptr_ty, ir::Expression::GlobalLoad(name.clone())))
+        if let Some(overloads) = self.globals.get(name) {
+            if overloads.len() == 1 {
+                Some(self.get_function(name, overloads[0].ty.clone()))
+            } else {
+                None
+            }
+        } else if let Some(lit) = self.consts.get(name) {
+            Some((lit.ty(), ir::Expression::Literal(lit.clone())))
         } else {
             None
         }
@@ -48,7 +154,8 @@ impl GlobalTable {
 #[derive(Debug, Clone)]
 pub struct SymbolTable<'a> {
     pub globals: &'a GlobalTable,
-    locals: Vec<HashMap<String, (ir::LocalVarId, ir::Type)>>,
+    // (id, type, mutable, declaration)
+    locals: Vec<HashMap<String, (ir::LocalVarId, ir::Type, bool, Span)>>,
 }
 
 impl<'a> SymbolTable<'a> {
@@ -67,22 +174,47 @@ impl<'a> SymbolTable<'a> {
         self.locals.pop();
     }
 
-    pub fn register_local(&mut self, name: String, ty: ir::Type, id: ir::LocalVarId) -> bool {
+    pub fn register_local(&mut self,
+                          name: String,
+                          ty: ir::Type,
+                          id: ir::LocalVarId,
+                          mutable: bool,
+                          span: Span)
+                          -> bool {
         // return false if already on scope
         self.locals
             .last_mut()
             .unwrap()
-            .insert(name, (id, ty))
+            .insert(name, (id, ty, mutable, span))
             .is_none()
     }
 
+    // None if the name doesn't refer to a local variable
+    pub fn is_mutable(&self, name: &String) -> Option<bool> {
+        self.get_local_mutability(name).map(|(mutable, _)| mutable)
+    }
+
+    // (mutable, declaration) of a local variable
+    pub fn get_local_mutability(&self, name: &String) -> Option<(bool, Span)> {
+        for scope in self.locals.iter().rev() {
+            if let Some(&(_, _, mutable, span)) = scope.get(name) {
+                return Some((mutable, span));
+            }
+        }
+        None
+    }
+
     pub fn get_var(&self, name: &String) -> Option<(ir::Type, ir::Expression)> {
         for scope in self.locals.iter().rev() {
-            if let Some(&(ref id, ref ty)) = scope.get(name) {
+            if let Some(&(ref id, ref ty, _, _)) = scope.get(name) {
                 return Some((ir::Type::LValue(Box::new(ty.clone())),
                              ir::Expression::LocalVarLoad(*id)));
             }
         }
         self.globals.get_var(name)
     }
+
+    pub fn is_defined(&self, name: &String) -> bool {
+        self.get_var(name).is_some() || self.globals.get_overloads(name).is_some()
+    }
 }
@@ -2,13 +2,16 @@ use std::fmt;
 
 use itertools::Itertools;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Type {
     Unit,
     Bool,
     Int,
     Double,
-    Char,
+    Char, // a code point
+    Byte,
+    Str, // (data, length), UTF-8 encoded by the literals
+    VaList, // pointer to the platform va_list
     LValue(Box<Type>),
     Ptr(Box<Type>),
     Function(FunctionType),
@@ -18,23 +21,24 @@ pub enum Type {
 impl Type {
     pub fn decay_type(&self) -> Option<Type> {
         match *self {
-            Type::Unit | Type::Bool | Type::Int | Type::Double | Type::Char | Type::LValue(_) |
-            Type::Ptr(_) => None,
+            Type::Unit | Type::Bool | Type::Int | Type::Double | Type::Char | Type::Byte |
+            Type::VaList | Type::LValue(_) | Type::Ptr(_) => None,
             ref other => Some(Type::LValue(Box::new(other.clone()))),
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct FunctionType {
     pub return_ty: Box<Type>,
     pub params_ty: Vec<Type>,
     pub variadic: bool,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct StructType {
     pub fields_ty: Vec<(String, Type)>,
+    pub packed: bool, // no padding between the fields
 }
 
 impl StructType {
@@ -56,6 +60,9 @@ impl fmt::Display for Type {
             Type::Int => write!(f, "int"),
             Type::Double => write!(f, "double"),
             Type::Char => write!(f, "char"),
+            Type::Byte => write!(f, "u8"),
+            Type::Str => write!(f, "str"),
+            Type::VaList => write!(f, "va_list"),
             Type::LValue(ref sub) => write!(f, "&{}", *sub),
             Type::Ptr(ref sub) => write!(f, "*{}", *sub),
             Type::Function(ref func) => write!(f, "{}", func),
@@ -77,7 +84,8 @@ impl fmt::Display for FunctionType {
 impl fmt::Display for StructType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f,
-               "struct{{ {} }}",
+               "{}struct{{ {} }}",
+               if self.packed { "packed " } else { "" },
                self.fields_ty
                    .iter()
                    .map(|&(ref name, ref ty)| format!("{}:{}", name, ty))
@@ -1,13 +1,14 @@
-mod comment_remover;
 mod token;
 
 use itertools::Itertools;
 use std::iter::Peekable;
-use std::str::FromStr;
+use std::str::{CharIndices, FromStr};
 
-use self::comment_remover::CommentRemover;
 pub use self::token::Token;
 
+// also reported by the parser diagnostics for a doc comment in an expression
+pub const UNATTACHED_DOC_COMMENT: &'static str = "Doc comment not attached to anything.";
+
 #[derive(Debug, Clone)]
 pub struct LexicalError {
     pub msg: String,
@@ -16,13 +17,15 @@ pub struct LexicalError {
 
 #[derive(Debug, Clone)]
 pub struct Lexer<'input> {
-    chars: Peekable<CommentRemover<'input>>,
+    chars: Peekable<CharIndices<'input>>,
+    attached_until: usize, // the doc comments before are followed by a token, see skip_trivia
 }
 
 impl<'input> Lexer<'input> {
     pub fn new(input: &'input str) -> Self {
         Lexer {
-            chars: CommentRemover::new(input).peekable()
+            chars: input.char_indices().peekable(),
+            attached_until: 0,
         }
     }
 
@@ -35,23 +38,192 @@ impl<'input> Lexer<'input> {
         }
         Err(false_tok)
     }
+
+    // after a quote, an identifier not closed by another quote is a loop label
+    fn next_is_label(&self) -> bool {
+        let mut ahead = self.chars.clone();
+        match ahead.next() {
+            Some((_, c)) if utils::is_identifier_start(c) => {
+                ahead.peek().map(|&(_, c)| c != '\'').unwrap_or(true)
+            }
+            _ => false,
+        }
+    }
+
+    // the character after the next one
+    fn second_char(&self) -> Option<char> {
+        let mut ahead = self.chars.clone();
+        ahead.next();
+        ahead.peek().map(|&(_, c)| c)
+    }
+
+    // eats the rest of the line, without the '\n'
+    fn eat_line(&mut self) -> String {
+        self.chars
+            .peeking_take_while(|&(_, c)| c != '\n')
+            .map(|(_, c)| c)
+            .collect()
+    }
+
+    // `/* ... */`, they can be nested
+    fn eat_block_comment(&mut self, start: usize) -> Result<(), LexicalError> {
+        self.chars.next(); // the '/'
+        self.chars.next(); // the '*'
+        let mut depth = 1;
+        while depth != 0 {
+            let next = self.chars.next().map(|(_, c)| c);
+            match (next, self.chars.peek().map(|&(_, c)| c)) {
+                (Some('*'), Some('/')) => {
+                    self.chars.next();
+                    depth -= 1;
+                }
+                (Some('/'), Some('*')) => {
+                    self.chars.next();
+                    depth += 1;
+                }
+                (Some(_), _) => {}
+                (None, _) => {
+                    return Err(LexicalError {
+                                   msg: String::from("Unterminated block comment."),
+                                   pos: start,
+                               })
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // skips the whitespaces and the comments, stops at a `///` doc comment if keep_docs
+    fn skip_trivia(&mut self,
+                   keep_docs: bool)
+                   -> Result<Option<(usize, Token, usize)>, LexicalError> {
+        while let Some(&(i, c)) = self.chars.peek() {
+            match (c, self.second_char()) {
+                // '#' starts a line comment, unless it opens an attribute
+                ('#', Some('[')) => break,
+                ('#', _) => {
+                    self.eat_line();
+                }
+                ('/', Some('/')) => {
+                    let line = self.eat_line();
+                    if keep_docs && line.starts_with("///") && !line.starts_with("////") {
+                        // a doc comment followed by the end of the input or of a block
+                        // documents nothing, the next token is only looked for once per block
+                        if i >= self.attached_until {
+                            match self.next_non_trivia() {
+                                Ok(Some((pos, c))) if c != '}' && c != ')' && c != ']' => {
+                                    self.attached_until = pos;
+                                }
+                                Err(_) => {} // reported when the lexer gets there
+                                _ => {
+                                    return Err(LexicalError {
+                                                   msg: String::from(UNATTACHED_DOC_COMMENT),
+                                                   pos: i,
+                                               })
+                                }
+                            }
+                        }
+                        let len = line.len();
+                        return Ok(Some((i, Token::DocComment(line[3..].to_string()), i + len)));
+                    }
+                }
+                ('/', Some('*')) => self.eat_block_comment(i)?,
+                (c, _) if c.is_whitespace() => {
+                    self.chars.next();
+                }
+                _ => break,
+            }
+        }
+        Ok(None)
+    }
+
+    // the first character after the whitespaces and all the comments, None at the end
+    fn next_non_trivia(&self) -> Result<Option<(usize, char)>, LexicalError> {
+        let mut ahead = self.clone();
+        ahead.skip_trivia(false)?;
+        Ok(ahead.chars.peek().cloned())
+    }
+
+    // after a 'r', `"` or `#"` starts a raw string
+    fn next_is_raw_string(&self) -> bool {
+        let mut ahead = self.chars.clone();
+        while let Some(&(_, '#')) = ahead.peek() {
+            ahead.next();
+        }
+        ahead.peek().map(|&(_, c)| c == '"').unwrap_or(false)
+    }
+
+    // `r#"..."#`, the content is escaped so that it's read as a normal string
+    // literal by the IR builder
+    fn raw_string(&mut self, start: usize) -> Result<(usize, Token, usize), LexicalError> {
+        let hashes = self.chars.peeking_take_while(|&(_, c)| c == '#').count();
+        self.chars.next(); // the '"'
+
+        let mut val = String::new();
+        while let Some((i, c)) = self.chars.next() {
+            if c == '"' {
+                let mut ahead = self.chars.clone();
+                if ahead.by_ref().take(hashes).filter(|&(_, c)| c == '#').count() == hashes {
+                    self.chars = ahead;
+                    return Ok((start, Token::StringLit(val), i + 1 + hashes));
+                }
+            }
+            if c == '\\' || c == '"' {
+                val.push('\\');
+            }
+            val.push(c);
+        }
+        Err(LexicalError {
+                msg: String::from("Unterminated raw string literal."),
+                pos: start,
+            })
+    }
+
+    // the content of a char or byte literal, after the opening quote
+    fn char_literal(&mut self, start: usize) -> Result<String, LexicalError> {
+        let mut val = String::new();
+        let mut slash = false;
+
+        val.extend(self.chars
+                       .peeking_take_while(|c| if slash {
+                                               slash = false;
+                                               true
+                                           } else if c.1 == '\\' {
+            slash = true;
+            true
+        } else if c.1 == '\'' {
+            false
+        } else {
+            true
+        })
+                       .map(|i| i.1));
+        if self.chars.next().is_none() {
+            return Err(LexicalError {
+                           msg: String::from("Unterminated character literal."),
+                           pos: start,
+                       });
+        }
+        Ok(val)
+    }
+
+    // a '.' followed by another '.' is a range, not a decimal point
+    fn next_is_decimal_point(&self) -> bool {
+        let mut ahead = self.chars.clone();
+        match ahead.next() {
+            Some((_, '.')) => ahead.peek().map(|&(_, c)| c != '.').unwrap_or(true),
+            _ => false,
+        }
+    }
 }
 
 impl<'input> Iterator for Lexer<'input> {
     type Item = Result<(usize, Token, usize), LexicalError>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let mut in_comment = false;
-        while let Some(&(_, c)) = self.chars.peek() {
-            if in_comment && c == '\n' {
-                in_comment = false;
-            } else if in_comment {
-            } else if c == '#' {
-                in_comment = true;
-            } else if !c.is_whitespace() {
-                break;
-            }
-            self.chars.next();
+        match self.skip_trivia(true) {
+            Ok(Some(doc)) => return Some(Ok(doc)),
+            Ok(None) => {}
+            Err(err) => return Some(Err(err)),
         }
 
         match self.chars.next() {
@@ -62,6 +234,11 @@ impl<'input> Iterator for Lexer<'input> {
             Some((i, '[')) => Some(Ok((i, Token::OpenSquare, i + 1))),
             Some((i, ']')) => Some(Ok((i, Token::CloseSquare, i + 1))),
 
+            Some((i, '#')) => {
+                self.chars.next(); // the '['
+                Some(Ok((i, Token::HashBracket, i + 2)))
+            }
+
             Some((i, ',')) => Some(Ok((i, Token::Comma, i + 1))),
             Some((i, ';')) => Some(Ok((i, Token::SemiColon, i + 1))),
             Some((i, ':')) => Some(Ok((i, Token::Colon, i + 1))),
@@ -105,10 +282,15 @@ impl<'input> Iterator for Lexer<'input> {
                 }
             }
             Some((i, '.')) => {
-                Some(Ok(match self.if_next('.', Token::DotDot, Token::Dot) {
-                            Ok(tok) => (i, tok, i + 2),
-                            Err(tok) => (i, tok, i + 1),
-                        }))
+                match self.if_next('.', Token::DotDot, Token::Dot) {
+                    Ok(_) => {
+                        Some(Ok(match self.if_next('=', Token::DotDotEqual, Token::DotDot) {
+                                    Ok(tok) => (i, tok, i + 3),
+                                    Err(tok) => (i, tok, i + 2),
+                                }))
+                    }
+                    Err(tok) => Some(Ok((i, tok, i + 1))),
+                }
             }
             Some((i, '<')) => {
                 Some(Ok(match self.if_next('=', Token::LessEqual, Token::Less) {
@@ -157,7 +339,7 @@ impl<'input> Iterator for Lexer<'input> {
                                .peeking_take_while(|c| c.1.is_digit(10))
                                .map(|i| i.1));
 
-                if let Some(&(_, '.')) = self.chars.peek() {
+                if self.next_is_decimal_point() {
                     lit.push('.');
                     self.chars.next();
                     lit.extend(self.chars
@@ -172,6 +354,14 @@ impl<'input> Iterator for Lexer<'input> {
                 }
 
             }
+            Some((i, 'r')) if self.next_is_raw_string() => Some(self.raw_string(i)),
+            Some((i, 'b')) if self.chars.peek().map(|&(_, c)| c == '\'').unwrap_or(false) => {
+                self.chars.next(); // the quote
+                Some(self.char_literal(i).map(|val| {
+                                                  let len = val.len() + 3;
+                                                  (i, Token::ByteLit(val), i + len)
+                                              }))
+            }
             Some((i, c)) if utils::is_identifier_start(c) => {
                 let mut id = c.to_string();
                 id.extend(self.chars
@@ -180,26 +370,19 @@ impl<'input> Iterator for Lexer<'input> {
                 let len = id.len();
                 Some(Ok((i, utils::identifier_or_keyword(id), i + len)))
             }
+            Some((i, '\'')) if self.next_is_label() => {
+                let mut id = String::new();
+                id.extend(self.chars
+                              .peeking_take_while(|c| utils::is_identifier_continue(c.1))
+                              .map(|i| i.1));
+                let len = id.len() + 1;
+                Some(Ok((i, Token::Label(id), i + len)))
+            }
             Some((i, '\'')) => {
-                let mut val = String::new();
-                let mut slash = false;
-
-                val.extend(self.chars
-                               .peeking_take_while(|c| if slash {
-                                                       slash = false;
-                                                       true
-                                                   } else if c.1 == '\\' {
-                    slash = true;
-                    true
-                } else if c.1 == '\'' {
-                    false
-                } else {
-                    true
-                })
-                               .map(|i| i.1));
-                self.chars.next();
-                let len = val.len() + 2;
-                Some(Ok((i, Token::CharLit(val), i + len)))
+                Some(self.char_literal(i).map(|val| {
+                                                  let len = val.len() + 2;
+                                                  (i, Token::CharLit(val), i + len)
+                                              }))
             }
             Some((i, '\"')) => {
                 let mut val = String::new();
@@ -218,7 +401,12 @@ impl<'input> Iterator for Lexer<'input> {
                         true
                     }
                 }).map(|i| i.1));
-                self.chars.next();
+                if self.chars.next().is_none() {
+                    return Some(Err(LexicalError {
+                                        msg: String::from("Unterminated string literal."),
+                                        pos: i,
+                                    }));
+                }
                 let len = val.len() + 2;
                 Some(Ok((i, Token::StringLit(val), i + len)))
             }
@@ -244,7 +432,9 @@ mod utils {
             "extern" => Token::ExternKeyword,
             "fn" => Token::FnKeyword,
             "let" => Token::LetKeyword,
+            "mut" => Token::MutKeyword,
             "for" => Token::ForKeyword,
+            "in" => Token::InKeyword,
             "while" => Token::WhileKeyword,
             "loop" => Token::LoopKeyword,
             "if" => Token::IfKeyword,
@@ -252,6 +442,13 @@ mod utils {
             "return" => Token::ReturnKeyword,
             "break" => Token::BreakKeyword,
             "continue" => Token::ContinueKeyword,
+            "defer" => Token::DeferKeyword,
+            "new" => Token::NewKeyword,
+            "delete" => Token::DeleteKeyword,
+            "va_arg" => Token::VaArgKeyword,
+            "sizeof" => Token::SizeOfKeyword,
+            "static_assert" => Token::StaticAssertKeyword,
+            "const" => Token::ConstKeyword,
             "as" => Token::AsKeyword,
             "true" => Token::BoolLit(true),
             "false" => Token::BoolLit(false),
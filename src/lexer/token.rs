@@ -5,7 +5,9 @@ pub enum Token {
     ExternKeyword,
     FnKeyword,
     LetKeyword,
+    MutKeyword,
     ForKeyword,
+    InKeyword,
     WhileKeyword,
     LoopKeyword,
     IfKeyword,
@@ -13,6 +15,13 @@ pub enum Token {
     ReturnKeyword,
     BreakKeyword,
     ContinueKeyword,
+    DeferKeyword,
+    NewKeyword,
+    DeleteKeyword,
+    VaArgKeyword,
+    SizeOfKeyword,
+    StaticAssertKeyword,
+    ConstKeyword,
     AsKeyword,
     OpenParen,
     CloseParen,
@@ -20,10 +29,12 @@ pub enum Token {
     CloseSquare,
     OpenBracket,
     CloseBracket,
+    HashBracket,
     Arrow,
     Comma,
     Dot,
     DotDot,
+    DotDotEqual,
     SemiColon,
     Colon,
     Plus,
@@ -51,6 +62,9 @@ pub enum Token {
     DoubleLit(f64),
     BoolLit(bool),
     CharLit(String),
+    ByteLit(String), // b'c'
     StringLit(String),
     Identifier(String),
+    Label(String),
+    DocComment(String), // the text after `///`
 }
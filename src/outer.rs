@@ -2,6 +2,7 @@ use std;
 use std::path::{Path, PathBuf};
 use std::fs::File;
 use std::io;
+use std::io::Write;
 
 use tempdir::TempDir;
 
@@ -9,6 +10,9 @@ use pipeline::{CompileOptions, OutputType};
 use ir;
 use codegen;
 
+// linked with every executable, see ir::runtime
+const RUNTIME_SOURCE: &'static str = include_str!("runtime/runtime.c");
+
 fn get_writer(output_path: &Option<PathBuf>) -> io::Result<Box<std::io::Write>> {
     if let Some(ref output_path) = *output_path {
         Ok(Box::new(std::fs::File::create(output_path)?))
@@ -113,16 +117,25 @@ fn output_exec(tu: ir::TranslationUnit,
     let llvm_path = output_llvm(tu, input_path, tmp_dir_path, options)?;
     let obj_path = tmp_dir_path.join(format!("{}.o", exec_name));
     let exec_path = tmp_dir_path.join(exec_name);
+    let runtime_path = tmp_dir_path.join("runtime.c");
+    File::create(&runtime_path)?.write_all(RUNTIME_SOURCE.as_bytes())?;
 
     if !run_command("llc",
-                    &["-filetype=obj", path_to_str(&llvm_path), "-o", path_to_str(&obj_path)])
+                    &["-filetype=obj",
+                      "-relocation-model=pic", // str constants, linked as PIE
+                      path_to_str(&llvm_path),
+                      "-o",
+                      path_to_str(&obj_path)])
                 ?
                 .success() {
         panic!("llc fail");
     }
 
     if !run_command("clang",
-                    &[path_to_str(&obj_path), "-o", path_to_str(&exec_path)])
+                    &[path_to_str(&obj_path),
+                      path_to_str(&runtime_path),
+                      "-o",
+                      path_to_str(&exec_path)])
                 ?
                 .success() {
         panic!("clang fail");
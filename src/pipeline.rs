@@ -36,10 +36,23 @@ pub struct CompileOptions {
     pub print_ast: bool,
     pub print_ir: bool,
     pub opt: bool,
+    pub release: bool, // asserts are compiled out
+    pub cfg: Vec<(String, Option<String>)>, // -D key[=value]
     pub output_type: OutputType,
     pub output_path: Option<PathBuf>,
 }
 
+impl CompileOptions {
+    // `debug` or `release` is always set
+    pub fn cfg_enabled(&self, key: &str, value: Option<&str>) -> bool {
+        let build_key = if self.release { "release" } else { "debug" };
+        (key == build_key && value.is_none()) ||
+        self.cfg
+            .iter()
+            .any(|&(ref k, ref v)| k == key && v.as_ref().map(|v| v.as_str()) == value)
+    }
+}
+
 pub fn build_path(id: &str, options: &CompileOptions) -> PathBuf {
     let mut path = options.global_dir.clone();
     path.push(format!("{}.li", id));
@@ -51,8 +64,13 @@ pub fn process_main_path<P: AsRef<Path>>(input_path: P,
                                          source_manager: &mut SourceManager)
                                          -> Result<ir::TranslationUnit, diagnostics::Error> {
     let mut globals_table = GlobalTable::new();
+    ir::runtime::register_runtime(&mut globals_table);
     let mut tu = process_path(input_path, options, source_manager, &mut globals_table)?;
 
+    let mut declarations = ir::runtime::declarations();
+    declarations.append(&mut tu.declarations);
+    tu.declarations = declarations;
+
     if options.opt {
         ir::opt::opt_translation_unit(&mut tu);
     }
@@ -81,13 +99,27 @@ pub fn process_path<P: AsRef<Path>>(input_path: P,
         }
     };
 
+    if let Err(err) = ast::cfg::strip_translation_unit(&mut ast_tu, options) {
+        return Err(err.convert(source_manager, input_index));
+    }
+
     if options.print_ast {
         ast::printer::print_ast(&ast_tu);
     }
 
     let declarations = process_imports(&mut ast_tu, options, source_manager, globals_table)?;
-    match ir::builder::build_translation_unit(ast_tu, declarations, globals_table) {
-        Ok(tu) => Ok(tu),
+    match ir::builder::build_translation_unit(ast_tu,
+                                              declarations,
+                                              globals_table,
+                                              options,
+                                              source_manager) {
+        Ok((tu, warnings)) => {
+            for warning in warnings {
+                let warning = warning.convert(source_manager, input_index);
+                diagnostics::print_warning(source_manager, warning);
+            }
+            Ok(tu)
+        }
         Err(err) => Err(err.convert(source_manager, input_index)),
     }
 }
@@ -0,0 +1,167 @@
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+// allocations are zeroed and never return NULL, calloc catches the overflow of count * size
+void *__elang_alloc(int32_t count, int32_t size) {
+    if (count < 0 || size < 0) {
+        fprintf(stderr, "elang: invalid allocation of %d items of size %d\n", count, size);
+        abort();
+    }
+
+    void *ptr = calloc(count == 0 ? 1 : (size_t)count, size == 0 ? 1 : (size_t)size);
+    if (ptr == NULL) {
+        fprintf(stderr, "elang: out of memory\n");
+        abort();
+    }
+    return ptr;
+}
+
+void __elang_free(void *ptr) {
+    free(ptr);
+}
+
+char __elang_str_at(const char *data, int32_t len, int32_t index) {
+    if (index < 0 || index >= len) {
+        fprintf(stderr, "elang: str index %d out of bounds (length %d)\n", index, len);
+        abort();
+    }
+    return data[index];
+}
+
+int32_t __elang_str_compare(const char *lhs, int32_t lhs_len, const char *rhs, int32_t rhs_len) {
+    int32_t min_len = lhs_len < rhs_len ? lhs_len : rhs_len;
+    int result = memcmp(lhs, rhs, min_len);
+    if (result != 0) {
+        return result < 0 ? -1 : 1;
+    }
+    return lhs_len < rhs_len ? -1 : lhs_len > rhs_len;
+}
+
+char *__elang_str_concat(const char *lhs, int32_t lhs_len, const char *rhs, int32_t rhs_len) {
+    char *data = __elang_alloc(lhs_len + rhs_len + 1, 1);
+    memcpy(data, lhs, lhs_len);
+    memcpy(data + lhs_len, rhs, rhs_len);
+    return data;
+}
+
+// literals and concatenations are already terminated
+char *__elang_str_c_str(const char *data, int32_t len) {
+    if (data[len] == '\0') {
+        return (char *)data;
+    }
+
+    char *copy = __elang_alloc(len + 1, 1);
+    memcpy(copy, data, len);
+    return copy;
+}
+
+// the length of the sequence, 0 if the code point is invalid
+static int32_t utf8_encode(int32_t c, char *buffer) {
+    if (c < 0 || c > 0x10FFFF || (c >= 0xD800 && c <= 0xDFFF)) {
+        return 0;
+    } else if (c < 0x80) {
+        buffer[0] = (char)c;
+        return 1;
+    } else if (c < 0x800) {
+        buffer[0] = (char)(0xC0 | (c >> 6));
+        buffer[1] = (char)(0x80 | (c & 0x3F));
+        return 2;
+    } else if (c < 0x10000) {
+        buffer[0] = (char)(0xE0 | (c >> 12));
+        buffer[1] = (char)(0x80 | ((c >> 6) & 0x3F));
+        buffer[2] = (char)(0x80 | (c & 0x3F));
+        return 3;
+    } else {
+        buffer[0] = (char)(0xF0 | (c >> 18));
+        buffer[1] = (char)(0x80 | ((c >> 12) & 0x3F));
+        buffer[2] = (char)(0x80 | ((c >> 6) & 0x3F));
+        buffer[3] = (char)(0x80 | (c & 0x3F));
+        return 4;
+    }
+}
+
+int32_t __elang_char_from_int(int32_t value) {
+    char buffer[4];
+    if (utf8_encode(value, buffer) == 0) {
+        fprintf(stderr, "elang: %d is not a valid char\n", value);
+        abort();
+    }
+    return value;
+}
+
+int32_t __elang_str_decode(const char *data, int32_t len, int32_t index) {
+    if (index < 0 || index >= len) {
+        fprintf(stderr, "elang: str index %d out of bounds (length %d)\n", index, len);
+        abort();
+    }
+
+    const unsigned char *bytes = (const unsigned char *)data + index;
+    int32_t available = len - index;
+    int32_t c, seq_len;
+    if (bytes[0] < 0x80) {
+        return bytes[0];
+    } else if ((bytes[0] & 0xE0) == 0xC0) {
+        c = bytes[0] & 0x1F;
+        seq_len = 2;
+    } else if ((bytes[0] & 0xF0) == 0xE0) {
+        c = bytes[0] & 0x0F;
+        seq_len = 3;
+    } else if ((bytes[0] & 0xF8) == 0xF0) {
+        c = bytes[0] & 0x07;
+        seq_len = 4;
+    } else {
+        seq_len = 0;
+    }
+
+    int32_t i;
+    for (i = 1; i < seq_len && i < available && (bytes[i] & 0xC0) == 0x80; i++) {
+        c = (c << 6) | (bytes[i] & 0x3F);
+    }
+    // overlong encodings are rejected by re-encoding the char
+    char buffer[4];
+    if (seq_len == 0 || i != seq_len || utf8_encode(c, buffer) != seq_len) {
+        fprintf(stderr, "elang: invalid UTF-8 at str index %d\n", index);
+        abort();
+    }
+    return c;
+}
+
+void __elang_print_str(const char *data, int32_t len) {
+    fwrite(data, 1, len, stdout);
+}
+
+void __elang_print_c_str(const char *str) {
+    fputs(str, stdout);
+}
+
+void __elang_print_int(int32_t value) {
+    printf("%d", value);
+}
+
+void __elang_print_double(double value) {
+    printf("%g", value);
+}
+
+void __elang_print_char(int32_t c) {
+    char buffer[4];
+    fwrite(buffer, 1, utf8_encode(c, buffer), stdout);
+}
+
+void __elang_print_bool(int32_t value) {
+    fputs(value ? "true" : "false", stdout);
+}
+
+void __elang_assert_fail(const char *file, int32_t file_len, int32_t line, int32_t column,
+                         const char *cond, int32_t cond_len,
+                         const char *msg, int32_t msg_len) {
+    fflush(stdout);
+    fprintf(stderr, "%.*s:%d:%d: assertion failed: %.*s",
+            file_len, file, line, column, cond_len, cond);
+    if (msg_len > 0) {
+        fprintf(stderr, ": %.*s", msg_len, msg);
+    }
+    fputc('\n', stderr);
+    abort();
+}
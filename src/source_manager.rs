@@ -3,11 +3,15 @@ use std::io::Read;
 use std::io;
 use std::path::{Path, PathBuf};
 
+use span::Span;
+
+#[derive(Debug)]
 struct SourceFile {
     path: PathBuf,
     content: String,
 }
 
+#[derive(Debug)]
 pub struct SourceManager {
     sources: Vec<SourceFile>
 }
@@ -36,6 +40,17 @@ impl SourceManager {
     pub fn get_file_path(&self, index: usize) -> &Path {
         &self.sources[index].path
     }
+
+    pub fn get_text(&self, span: Span) -> &str {
+        &self.get_input(span.source_index)[span.lo..span.hi]
+    }
+
+    // 1-based, pos is a byte position like in spans
+    pub fn get_line_column(&self, index: usize, pos: usize) -> (usize, usize) {
+        let before = &self.get_input(index)[..pos];
+        let line_start = before.rfind('\n').map(|p| p + 1).unwrap_or(0);
+        (before.matches('\n').count() + 1, before[line_start..].chars().count() + 1)
+    }
 }
 
 fn slurp_file<P: AsRef<Path>>(path: P) -> io::Result<String> {
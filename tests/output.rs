@@ -1,28 +1,65 @@
 extern crate elang;
-extern crate itertools;
 extern crate tempdir;
 
 use tempdir::TempDir;
-use itertools::Itertools;
 
 use std::fs::File;
 use std::io::prelude::*;
 use std::io::BufReader;
 use std::path::PathBuf;
 
-fn read_expected_output(path: &str) -> String {
+// the leading `//` lines: the expected output, and the `//-D key[=value]`
+// and `//--release` options of the compiler, a `//@case ` line only belongs to
+// the header of that case, which is compiled with `-D case`
+struct Header {
+    expected_output: String,
+    cfg: Vec<(String, Option<String>)>,
+    release: bool,
+}
+
+fn read_header(path: &str) -> Header {
+    read_case_header(path, None)
+}
+
+fn read_case_header(path: &str, case: Option<&str>) -> Header {
     let file = File::open(path).expect("io error");
     let reader = BufReader::new(file);
 
-    reader
-        .lines()
-        .map(|r| r.expect("io error"))
-        .take_while(|line| line.starts_with("//"))
-        .map(|line| format!("{}\n", &line[2..]))
-        .join("")
+    let mut header = Header {
+        expected_output: String::new(),
+        cfg: Vec::new(),
+        release: false,
+    };
+    if let Some(case) = case {
+        header.cfg.push((case.to_string(), None));
+    }
+    for line in reader.lines().map(|r| r.expect("io error")) {
+        if !line.starts_with("//") {
+            break;
+        }
+        let mut line = &line[2..];
+        if line.starts_with('@') {
+            let end = line.find(' ').expect("empty case line");
+            if Some(&line[1..end]) != case {
+                continue;
+            }
+            line = &line[end + 1..];
+        }
+        if line.starts_with("-D ") {
+            let mut parts = line[3..].splitn(2, '=');
+            header
+                .cfg
+                .push((parts.next().unwrap().to_string(), parts.next().map(String::from)));
+        } else if line == "--release" {
+            header.release = true;
+        } else {
+            header.expected_output.push_str(&format!("{}\n", line));
+        }
+    }
+    header
 }
 
-fn run(path: &str) -> String {
+fn run(path: &str, header: &Header) -> std::process::Output {
     use elang::pipeline;
     use elang::outer;
     use elang::pipeline::{CompileOptions, OutputType};
@@ -37,6 +74,8 @@ fn run(path: &str) -> String {
         print_ast: false,
         print_ir: false,
         opt: true,
+        release: header.release,
+        cfg: header.cfg.clone(),
         output_type: OutputType::Exec,
         output_path: Some(exec_path.to_path_buf())
     };
@@ -45,9 +84,29 @@ fn run(path: &str) -> String {
     let tu = pipeline::process_main_path(path, &options, &mut source_manager).expect("diag error");
     outer::main_outer(tu, path, &options).expect("outer error");
 
-    let output = std::process::Command::new(exec_path).output().expect("io error");
+    std::process::Command::new(exec_path).output().expect("io error")
+}
 
-    String::from_utf8_lossy(&output.stdout).into_owned()
+// the errors and warnings printed by the driver, without the trailing spaces of the arrows
+fn check(path: &str, header: &Header) -> (bool, String) {
+    let mut command = std::process::Command::new(env!("CARGO_BIN_EXE_elang_driver"));
+    command.arg(path).arg("-t").arg("check");
+    for &(ref key, ref value) in &header.cfg {
+        match *value {
+            Some(ref value) => command.arg("-D").arg(format!("{}={}", key, value)),
+            None => command.arg("-D").arg(key),
+        };
+    }
+    if header.release {
+        command.arg("--release");
+    }
+
+    let output = command.output().expect("io error");
+    let diagnostics = String::from_utf8_lossy(&output.stderr)
+        .lines()
+        .map(|line| format!("{}\n", line.trim_end()))
+        .collect();
+    (output.status.success(), diagnostics)
 }
 
 macro_rules! output_test {
@@ -55,15 +114,168 @@ macro_rules! output_test {
         #[test]
         fn $name() {
             let path = $path;
-            let expected_output = read_expected_output(path);
-            let found_output = run(path);
+            let header = read_header(path);
+            let output = run(path, &header);
 
-            assert_eq!(expected_output, found_output);
+            assert_eq!(header.expected_output, String::from_utf8_lossy(&output.stdout));
         }
     }
 }
 
+output_test!(assert, "tests/output/assert.li");
+output_test!(assert_release, "tests/output/assert_release.li");
+output_test!(attributes, "tests/output/attributes.li");
+output_test!(cfg, "tests/output/cfg.li");
+output_test!(comments, "tests/output/comments.li");
+output_test!(const_fn, "tests/output/const_fn.li");
+output_test!(consts, "tests/output/consts.li");
+output_test!(default_args, "tests/output/default_args.li");
+output_test!(defer, "tests/output/defer.li");
+output_test!(escapes, "tests/output/escapes.li");
+output_test!(extern_overload, "tests/output/extern_overload.li");
+output_test!(for_range, "tests/output/for_range.li");
 output_test!(func, "tests/output/func.li");
+output_test!(heap, "tests/output/heap.li");
+output_test!(if_expr, "tests/output/if_expr.li");
+output_test!(labeled_loops, "tests/output/labeled_loops.li");
+output_test!(loop_expr, "tests/output/loop_expr.li");
+output_test!(mutability, "tests/output/mutability.li");
+output_test!(operators, "tests/output/operators.li");
+output_test!(overloading, "tests/output/overloading.li");
 output_test!(parentheses, "tests/output/parentheses.li");
 output_test!(precedence, "tests/output/precedence.li");
 output_test!(primes, "tests/output/primes.li");
+output_test!(print, "tests/output/print.li");
+output_test!(printf, "tests/output/printf.li");
+output_test!(static_assert, "tests/output/static_assert.li");
+output_test!(strings, "tests/output/strings.li");
+output_test!(struct_eq, "tests/output/struct_eq.li");
+output_test!(struct_update, "tests/output/struct_update.li");
+output_test!(unicode, "tests/output/unicode.li");
+output_test!(variadic, "tests/output/variadic.li");
+
+// the header holds the expected stdout followed by the stderr of an exec which must fail
+macro_rules! failure_test {
+    ( $name:ident, $path:expr ) => {
+        #[test]
+        fn $name() {
+            let path = $path;
+            let header = read_header(path);
+            let output = run(path, &header);
+            let found_output = format!("{}{}",
+                                       String::from_utf8_lossy(&output.stdout),
+                                       String::from_utf8_lossy(&output.stderr));
+
+            assert_eq!(header.expected_output, found_output);
+            assert!(!output.status.success());
+        }
+    }
+}
+
+failure_test!(assert_failure, "tests/output/assert_failure.li");
+
+// the header holds the expected diagnostics, the compilation fails if one is an error
+macro_rules! diagnostic_test {
+    ( $name:ident, $path:expr ) => {
+        #[test]
+        fn $name() {
+            let path = $path;
+            let header = read_header(path);
+            let (success, diagnostics) = check(path, &header);
+
+            assert_eq!(header.expected_output, diagnostics);
+            assert_eq!(!diagnostics.contains("Error in:"), success);
+        }
+    };
+    ( $name:ident, $path:expr, $case:expr ) => {
+        #[test]
+        fn $name() {
+            let path = $path;
+            let header = read_case_header(path, Some($case));
+            let (success, diagnostics) = check(path, &header);
+
+            assert_eq!(header.expected_output, diagnostics);
+            assert_eq!(!diagnostics.contains("Error in:"), success);
+        }
+    };
+}
+
+diagnostic_test!(ambiguous_default_values, "tests/diagnostics/ambiguous_default_values.li");
+diagnostic_test!(assert_release_types, "tests/diagnostics/assert_release_types.li");
+diagnostic_test!(break_value_mismatching_types,
+                 "tests/diagnostics/break_value_errors.li",
+                 "mismatching_types");
+diagnostic_test!(break_value_outside_loop,
+                 "tests/diagnostics/break_value_errors.li",
+                 "outside_loop");
+diagnostic_test!(const_division_overflow, "tests/diagnostics/const_division_overflow.li");
+diagnostic_test!(const_eval_division_by_zero,
+                 "tests/diagnostics/const_eval_errors.li",
+                 "division_by_zero");
+diagnostic_test!(const_eval_extern_call,
+                 "tests/diagnostics/const_eval_errors.li",
+                 "extern_call");
+diagnostic_test!(const_eval_memory_limit,
+                 "tests/diagnostics/const_eval_errors.li",
+                 "memory_limit");
+diagnostic_test!(const_eval_recursion_limit,
+                 "tests/diagnostics/const_eval_errors.li",
+                 "recursion_limit");
+diagnostic_test!(const_eval_step_limit,
+                 "tests/diagnostics/const_eval_errors.li",
+                 "step_limit");
+diagnostic_test!(const_function_cycle, "tests/diagnostics/const_function_cycle.li");
+diagnostic_test!(delete_str_literal, "tests/diagnostics/delete_str_literal.li");
+diagnostic_test!(doc_comment_in_expression, "tests/diagnostics/doc_comment_in_expression.li");
+diagnostic_test!(export_name_collision, "tests/diagnostics/export_name_collision.li");
+diagnostic_test!(immutable_address_of, "tests/diagnostics/immutable_address_of.li");
+diagnostic_test!(immutable_array, "tests/diagnostics/immutable_array.li");
+diagnostic_test!(immutable_array_alias, "tests/diagnostics/immutable_array_alias.li");
+diagnostic_test!(immutable_assignment, "tests/diagnostics/immutable_assignment.li");
+diagnostic_test!(named_argument_argument_already_supplied,
+                 "tests/diagnostics/named_argument_errors.li",
+                 "argument_already_supplied");
+diagnostic_test!(named_argument_missing_parameter,
+                 "tests/diagnostics/named_argument_errors.li",
+                 "missing_parameter");
+diagnostic_test!(named_argument_positional_after_named,
+                 "tests/diagnostics/named_argument_errors.li",
+                 "positional_after_named");
+diagnostic_test!(named_argument_unknown_parameter,
+                 "tests/diagnostics/named_argument_errors.li",
+                 "unknown_parameter");
+diagnostic_test!(overload_ambiguous_call,
+                 "tests/diagnostics/overload_errors.li",
+                 "ambiguous_call");
+diagnostic_test!(overload_ambiguous_overload,
+                 "tests/diagnostics/overload_errors.li",
+                 "ambiguous_overload");
+diagnostic_test!(overload_no_matching_overload,
+                 "tests/diagnostics/overload_errors.li",
+                 "no_matching_overload");
+diagnostic_test!(printf_mismatch, "tests/diagnostics/printf_mismatch.li");
+diagnostic_test!(static_assert_failed_declaration,
+                 "tests/diagnostics/static_assert_failed.li",
+                 "declaration");
+diagnostic_test!(static_assert_failed_declaration_message,
+                 "tests/diagnostics/static_assert_failed.li",
+                 "declaration_message");
+diagnostic_test!(static_assert_failed_function,
+                 "tests/diagnostics/static_assert_failed.li",
+                 "function");
+diagnostic_test!(static_assert_failed_function_message,
+                 "tests/diagnostics/static_assert_failed.li",
+                 "function_message");
+diagnostic_test!(struct_literal_base_type,
+                 "tests/diagnostics/struct_literal_errors.li",
+                 "base_type");
+diagnostic_test!(struct_literal_field_already_initialized,
+                 "tests/diagnostics/struct_literal_errors.li",
+                 "field_already_initialized");
+diagnostic_test!(struct_literal_field_type,
+                 "tests/diagnostics/struct_literal_errors.li",
+                 "field_type");
+diagnostic_test!(struct_literal_uninitialized_fields,
+                 "tests/diagnostics/struct_literal_errors.li",
+                 "uninitialized_fields");
+diagnostic_test!(undefined_label, "tests/diagnostics/undefined_label.li");
@@ -0,0 +1,46 @@
+extern crate elang;
+extern crate lalrpop_util;
+
+use elang::lexer::{Lexer, UNATTACHED_DOC_COMMENT};
+use elang::parser;
+use lalrpop_util::ParseError;
+
+fn parse_error(input: &str) -> String {
+    match parser::parse_TranslationUnit(0, Lexer::new(input)) {
+        Ok(_) => panic!("no error"),
+        Err(ParseError::User { error }) => error.msg,
+        Err(err) => format!("{:?}", err),
+    }
+}
+
+#[test]
+fn doc_comments() {
+    let input = "/// Adds two numbers.\n///\n///Both are ints.\n\
+                 fn add(a: int, b: int) -> int { return a + b; }\n\
+                 #[doc = \" A point.\"]\n#[packed]\nstruct Point { x: int, }\n\
+                 const ZERO = 0;\n";
+    let tu = parser::parse_TranslationUnit(0, Lexer::new(input)).expect("parse error");
+    let docs: Vec<_> = tu.declarations.iter().map(|decl| decl.inner.doc()).collect();
+
+    assert_eq!(docs,
+               vec![Some(String::from("Adds two numbers.\n\nBoth are ints.")),
+                    Some(String::from("A point.")),
+                    None]);
+}
+
+// each line used to look ahead through the next ones
+#[test]
+fn long_doc_comments() {
+    let input = format!("{}fn main() {{}}\n", "/// line\n".repeat(64));
+    let tu = parser::parse_TranslationUnit(0, Lexer::new(&input)).expect("parse error");
+
+    assert_eq!(tu.declarations[0].inner.doc().map(|doc| doc.lines().count()), Some(64));
+}
+
+#[test]
+fn unattached_doc_comments() {
+    assert_eq!(parse_error("fn main() {}\n/// at the end\n"), UNATTACHED_DOC_COMMENT);
+    assert_eq!(parse_error("fn main() {\n    /// before the brace\n}\n"), UNATTACHED_DOC_COMMENT);
+    assert_eq!(parse_error("struct S {\n    x: int,\n    /// no field\n}\n"),
+               UNATTACHED_DOC_COMMENT);
+}